@@ -0,0 +1,4309 @@
+/*
+ * Copyright (C) 2023 Calum Murray (cmurray@redhat.com)
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliace with the License.
+ * You may obtain a copy of the License at
+ *
+ *         http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Parsing and comparison of JMH benchmark results.
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    error::Error,
+    fmt::Display,
+    fmt::{Formatter, Result as FmtResult},
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+    str::FromStr,
+};
+
+use flate2::bufread::GzDecoder;
+use regex::Regex;
+use serde_json::{json, Value};
+use tabled::Tabled;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Mode {
+    AverageTime,
+    SampleTime,
+    SingleShotTime,
+    Throughput,
+}
+
+/// Where in the input a [`ParseError`] happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    /// A line of a text table, starting from 1.
+    Line(usize),
+    /// A benchmark in a JSON array, starting from 1.
+    Entry(usize),
+    /// A row parsed on its own, which isn't part of a file.
+    Row,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    InvalidMode {
+        at: Location,
+        token: String,
+    },
+    InvalidFloat {
+        at: Location,
+        token: String,
+    },
+    InvalidInt {
+        at: Location,
+        token: String,
+    },
+    MissingName {
+        at: Location,
+    },
+    MissingField {
+        at: Location,
+        field: &'static str,
+    },
+    InvalidJson {
+        message: String,
+    },
+    MissingMetric {
+        at: Location,
+    },
+    DuplicateBenchmark {
+        at: Location,
+        name: String,
+        mode: Mode,
+    },
+    /// JMH reports `NaN` or `∞` for measurements that failed.
+    NonFiniteScore {
+        at: Location,
+        token: String,
+    },
+}
+
+/// The rows that couldn't be parsed, by the file they're from, for rendering diagnostics.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diagnostics {
+    pub files: Vec<(String, Vec<ParseError>)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Tabled)]
+pub struct BenchResult {
+    pub name: String,
+    /// The values of the JMH `@Param` fields the benchmark was run with, in column order.
+    #[tabled(display_with = "format_params")]
+    pub params: Vec<(String, String)>,
+    pub mode: Mode,
+    /// The number of threads the benchmark ran with, or 0 if the output doesn't say.
+    #[tabled(display_with = "format_threads")]
+    pub threads: i64,
+    pub count: i64,
+    pub score: f64,
+    pub error: f64,
+    pub units: String,
+    /// The latency percentiles JMH records for sample time benchmarks in JSON output.
+    #[tabled(skip)]
+    pub percentiles: Option<Percentiles>,
+    /// The secondary metrics from JSON output, like `gc.alloc.rate.norm`, by name.
+    #[tabled(skip)]
+    pub secondary_metrics: Vec<(String, SecondaryMetric)>,
+    /// The score of every measurement iteration of every fork, from the raw data in JSON
+    /// output.
+    #[tabled(skip)]
+    pub raw_data: Vec<f64>,
+    /// The score of the last warmup iteration of every fork, from the log JMH prints before
+    /// the results table.
+    #[tabled(skip)]
+    pub warmup_scores: Vec<f64>,
+}
+
+/// A metric JMH records alongside the score, usually from a profiler.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecondaryMetric {
+    pub score: f64,
+    pub error: f64,
+    pub units: String,
+}
+
+/// Percentiles of a sample time benchmark, in the units of its score.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Percentiles {
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub p999: f64,
+}
+
+/// The change of a benchmark between the old and new results.
+///
+/// Equality compares the `f64` fields, so a diff with a `NaN` in it isn't equal to anything,
+/// not even itself. Parsed scores are never `NaN`, but the diff of hand-built results can be.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchDiff {
+    pub name: String,
+    pub params: Vec<(String, String)>,
+    pub mode: Mode,
+    pub old_score: f64,
+    pub new_score: f64,
+    pub units: String,
+    pub diff: f64,
+    pub old_error: f64,
+    pub new_error: f64,
+    /// The number of measurement iterations, which can explain a change in the score if the
+    /// harness was configured differently.
+    pub old_count: i64,
+    pub new_count: i64,
+    /// The number of threads, or 0 if the output doesn't say. A different number of threads
+    /// makes the scores hard to compare, especially for throughput.
+    pub old_threads: i64,
+    pub new_threads: i64,
+    /// The error of `diff`, propagated from the errors of the old and new scores.
+    pub diff_error: f64,
+    /// The relative change of the 99th percentile, if both results have percentiles.
+    pub p99_diff: Option<f64>,
+    /// False if the confidence intervals of the old and new scores overlap.
+    pub significant: bool,
+    /// True if the diff is within the noise band, so it's shown as unchanged.
+    pub noise: bool,
+    /// True if the error of the old or new score is too large a part of it for the diff to
+    /// mean much.
+    pub high_variance: bool,
+    /// True if lower scores are better for a throughput benchmark, or higher ones for the
+    /// time modes, because the harness doesn't report what the mode says.
+    pub inverted: bool,
+}
+
+/// The outcome of looking up a benchmark in both result sets.
+// There's one comparison per benchmark, so boxing the results wouldn't save much
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum BenchComparison {
+    /// The benchmark only exists in the new results.
+    Added(BenchResult),
+    /// The benchmark only exists in the old results.
+    Removed(BenchResult),
+    /// The benchmark exists in both results.
+    Changed(BenchDiff),
+    /// The benchmark exists in both results, but in units that can't be converted.
+    UnitMismatch { old: BenchResult, new: BenchResult },
+}
+
+/// The results of one benchmark across several runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchTrend {
+    pub name: String,
+    pub params: Vec<(String, String)>,
+    pub mode: Mode,
+    /// The units scores are shown in, taken from the first run with this benchmark.
+    pub units: String,
+    /// One result per run, `None` if the benchmark is missing from that run.
+    pub runs: Vec<Option<BenchResult>>,
+}
+
+/// Aggregate statistics over a set of diffs.
+///
+/// The mean and median are of the mode-aware improvement, so a positive value means the new
+/// results are better on average. Infinite diffs are left out of both.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    pub compared: usize,
+    pub improved: usize,
+    pub regressed: usize,
+    pub mean: f64,
+    pub median: f64,
+    /// The geometric mean of the speedups minus one, which unlike the mean of the diffs
+    /// weighs a benchmark getting twice as fast the same as one getting twice as slow.
+    pub geomean: f64,
+    /// The number of diffs in the geometric mean, which leaves out those with a zero score.
+    pub geomean_count: usize,
+}
+
+/// How scores are written in tables.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NumberFormat {
+    /// A fixed number of decimal places, or `None` to round to three significant figures.
+    pub precision: Option<usize>,
+    /// Scale large numbers down with a k, M or G suffix.
+    pub si_suffixes: bool,
+    pub diff_style: DiffStyle,
+}
+
+/// What the diff column shows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiffStyle {
+    /// The relative change, like `+5.00000%`.
+    #[default]
+    Ratio,
+    /// The new score minus the old one, like `+1200 ops/s`.
+    Absolute,
+    /// The absolute change followed by the relative one, like `+1200 ops/s (+5.00000%)`.
+    Both,
+}
+
+/// The character scores use as a decimal point, which depends on the locale JMH ran in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DecimalSeparator {
+    /// Like `1234.5`, which also allows a comma between thousands, like `1,234.5`.
+    #[default]
+    Point,
+    /// Like `1234,5`, which also allows a point between thousands, like `1.234,5`.
+    Comma,
+}
+
+/// How an absolute threshold combines with the percentage one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ThresholdLogic {
+    /// A regression has to exceed both thresholds.
+    #[default]
+    And,
+    /// A regression has to exceed either threshold.
+    Or,
+}
+
+impl Display for ThresholdLogic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::And => write!(f, "and"),
+            Self::Or => write!(f, "or"),
+        }
+    }
+}
+
+/// Regression thresholds in percent, for the benchmarks matching name patterns where `*`
+/// matches any characters.
+#[derive(Debug, Default)]
+pub struct Thresholds {
+    /// The threshold for benchmarks none of the patterns match, instead of the default for
+    /// their mode.
+    pub default: Option<f64>,
+    /// A threshold in the units of each benchmark, for benchmarks so close to zero that
+    /// percentages don't mean much.
+    pub absolute: Option<f64>,
+    pub logic: ThresholdLogic,
+    patterns: Vec<ThresholdPattern>,
+}
+
+#[derive(Debug)]
+struct ThresholdPattern {
+    regex: Regex,
+    /// Whether the pattern has no `*`, which makes it more specific than any that do.
+    exact: bool,
+    /// The number of characters other than `*`, the more the more specific the pattern is.
+    specificity: usize,
+    threshold: f64,
+}
+
+/// How to order the rows of a comparison.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Keep the order of the old file, followed by added benchmarks.
+    None,
+    /// By name, then by mode, so the order doesn't depend on the order of the files.
+    Name,
+    /// Biggest regression first.
+    Diff,
+}
+
+/// A statistic of the iteration scores that can be compared instead of the mean JMH reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Statistic {
+    Mean,
+    /// Less sensitive to outliers than the mean.
+    Median,
+    Min,
+    Max,
+}
+
+/// A benchmark that was measured under a different mode in the new results.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModeChange {
+    pub name: String,
+    pub old_mode: Mode,
+    pub new_mode: Mode,
+}
+
+/// The version of JMH that produced a results file, like `1.37`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct JmhVersion(Vec<u32>);
+
+impl Mode {
+    pub fn higher_is_better(&self) -> bool {
+        matches!(self, Self::Throughput)
+    }
+
+    /// The regression threshold in percent used when none is given, higher for the modes
+    /// that tend to be noisier.
+    pub fn default_threshold(&self) -> f64 {
+        match self {
+            Self::Throughput => 3.0,
+            Self::AverageTime | Self::SampleTime => 2.0,
+            Self::SingleShotTime => 1.0,
+        }
+    }
+}
+
+impl Thresholds {
+    pub fn new(default: Option<f64>) -> Self {
+        Self {
+            default,
+            ..Self::default()
+        }
+    }
+
+    /// Adds a threshold for the benchmarks matching the pattern. Patterns are matched against
+    /// the end of the name, so `MyBench.run` matches `com.example.MyBench.run`.
+    pub fn add(&mut self, pattern: &str, threshold: f64) {
+        let parts: Vec<String> = pattern.split('*').map(regex::escape).collect();
+        let regex = Regex::new(&format!(r"(^|\.){}$", parts.join(".*")))
+            .expect("escaped pattern should be a valid regex");
+        self.patterns.push(ThresholdPattern {
+            regex,
+            exact: !pattern.contains('*'),
+            specificity: pattern.chars().filter(|&c| c != '*').count(),
+            threshold,
+        });
+    }
+
+    /// Adds the thresholds from a TOML config file, which maps patterns to thresholds like
+    /// `"MyBench.hotPath" = 2.0`, either at the top level or in a `[thresholds]` table.
+    pub fn parse_toml(&mut self, input: &str) -> Result<(), String> {
+        let config: toml::Table = input.parse().map_err(|e: toml::de::Error| e.to_string())?;
+        for (key, value) in &config {
+            match value {
+                toml::Value::Table(thresholds) if key == "thresholds" => {
+                    for (pattern, threshold) in thresholds {
+                        self.add(pattern, toml_threshold(pattern, threshold)?);
+                    }
+                }
+                _ => self.add(key, toml_threshold(key, value)?),
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the threshold of the most specific pattern matching the name of the diff. If
+    /// none of them match it's the default, or the default for the mode if there isn't one.
+    pub fn get(&self, diff: &BenchDiff) -> f64 {
+        self.patterns
+            .iter()
+            .filter(|p| p.regex.is_match(&diff.name))
+            .max_by_key(|p| (p.exact, p.specificity))
+            .map(|p| p.threshold)
+            .or(self.default)
+            .unwrap_or_else(|| diff.mode.default_threshold())
+    }
+
+    /// Returns true if the diff isn't noise and is a regression of more than its threshold,
+    /// combined with the absolute threshold if there is one.
+    pub fn exceeded_by(&self, diff: &BenchDiff) -> bool {
+        if diff.noise {
+            return false;
+        }
+        let relative = diff.exceeds_threshold(self.get(diff));
+        let Some(absolute) = self.absolute else {
+            return relative;
+        };
+        let absolute = diff.absolute_regression() > absolute;
+        match self.logic {
+            ThresholdLogic::And => relative && absolute,
+            ThresholdLogic::Or => relative || absolute,
+        }
+    }
+
+    /// Describes the thresholds of the diff, like `3%` or `3% and 0.5 ns/op`.
+    pub fn describe(&self, diff: &BenchDiff) -> String {
+        match self.absolute {
+            Some(absolute) => format!(
+                "{}% {} {absolute} {}",
+                self.get(diff),
+                self.logic,
+                diff.units
+            ),
+            None => format!("{}%", self.get(diff)),
+        }
+    }
+
+    /// Returns true if the diff isn't noise and is an improvement or regression of more than
+    /// its threshold.
+    pub fn changed_by(&self, diff: &BenchDiff) -> bool {
+        !diff.noise && diff.improvement().abs() * 100.0 > self.get(diff)
+    }
+}
+
+/// Reads the threshold for a pattern in a TOML config file, which has to be a number.
+fn toml_threshold(pattern: &str, value: &toml::Value) -> Result<f64, String> {
+    value
+        .as_float()
+        .or_else(|| value.as_integer().map(|t| t as f64))
+        .ok_or_else(|| format!("the threshold for '{pattern}' should be a number"))
+}
+
+impl NumberFormat {
+    const SIGNIFICANT_FIGURES: i32 = 3;
+
+    pub fn format(&self, value: f64) -> String {
+        if !value.is_finite() {
+            return value.to_string();
+        }
+
+        let (value, suffix) = match value.abs() {
+            v if self.si_suffixes && v >= 1e9 => (value / 1e9, "G"),
+            v if self.si_suffixes && v >= 1e6 => (value / 1e6, "M"),
+            v if self.si_suffixes && v >= 1e3 => (value / 1e3, "k"),
+            _ => (value, ""),
+        };
+
+        let decimals = self.precision.unwrap_or_else(|| {
+            if value == 0.0 {
+                return 0;
+            }
+            let magnitude = value.abs().log10().floor() as i32;
+            (Self::SIGNIFICANT_FIGURES - 1 - magnitude).max(0) as usize
+        });
+
+        format!("{value:.decimals$}{suffix}")
+    }
+}
+
+fn format_threads(threads: &i64) -> String {
+    match threads {
+        0 => "-".to_string(),
+        threads => threads.to_string(),
+    }
+}
+
+fn format_params(params: &[(String, String)]) -> String {
+    params
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// Returns the benchmark name with its parameters, like `MyBench.run[size=1024]`.
+fn display_name(name: &str, params: &[(String, String)]) -> String {
+    if params.is_empty() {
+        name.to_string()
+    } else {
+        format!("{name}[{}]", format_params(params))
+    }
+}
+
+fn params_json(params: &[(String, String)]) -> Value {
+    Value::Object(
+        params
+            .iter()
+            .map(|(name, value)| (name.clone(), Value::String(value.clone())))
+            .collect(),
+    )
+}
+
+impl BenchResult {
+    pub fn display_name(&self) -> String {
+        display_name(&self.name, &self.params)
+    }
+
+    /// Returns the result measured the other way around, as average time instead of throughput
+    /// or the reverse, so `2 ops/s` becomes `0.5 s/op`.
+    pub fn reciprocal(&self) -> Option<BenchResult> {
+        let (mode, units) = match self.mode {
+            Mode::Throughput => {
+                let time = self.units.strip_prefix("ops/")?;
+                (Mode::AverageTime, format!("{time}/op"))
+            }
+            Mode::AverageTime => {
+                let time = self.units.strip_suffix("/op")?;
+                (Mode::Throughput, format!("ops/{time}"))
+            }
+            _ => return None,
+        };
+        if self.score == 0.0 {
+            return None;
+        }
+
+        Some(BenchResult {
+            mode,
+            units,
+            score: 1.0 / self.score,
+            // The relative error of the reciprocal is the same
+            error: self.error / (self.score * self.score),
+            percentiles: None,
+            secondary_metrics: vec![],
+            raw_data: self.raw_data.iter().map(|s| 1.0 / s).collect(),
+            warmup_scores: self.warmup_scores.iter().map(|s| 1.0 / s).collect(),
+            ..self.clone()
+        })
+    }
+
+    /// Returns how far the mean of the last warmup iterations is from the score, relative to
+    /// the score, or `None` if there are no warmup scores. A large gap means the benchmark
+    /// might not have been warmed up.
+    pub fn warmup_gap(&self) -> Option<f64> {
+        let warmup = Statistic::Mean.of(&self.warmup_scores)?;
+        Some(relative_diff(self.score, warmup))
+    }
+
+    /// Returns true if both results are for the same benchmark, run in the same way.
+    fn same_key(&self, other: &BenchResult) -> bool {
+        self.name == other.name
+            && self.params == other.params
+            && self.mode == other.mode
+            && self.units == other.units
+    }
+}
+
+/// Formats a relative diff as a signed percentage.
+fn diff_percent(diff: f64) -> String {
+    if diff.is_infinite() {
+        format!("{}Inf%", if diff > 0.0 { "+" } else { "-" })
+    } else {
+        format!("{:+.5}%", diff * 100.0)
+    }
+}
+
+/// Quotes a CSV field as described in RFC 4180 if it needs it.
+fn csv_escape(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\r', '\n']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+impl BenchDiff {
+    pub fn display_name(&self) -> String {
+        display_name(&self.name, &self.params)
+    }
+
+    pub const CSV_HEADER: &'static str = "name,mode,old_score,new_score,units,diff";
+
+    pub fn diff_str(&self) -> String {
+        diff_percent(self.diff)
+    }
+
+    /// The size of the change in percent, like `1.00000%`, for messages that already say
+    /// whether it was an improvement or a regression, where the sign of the diff would
+    /// contradict them in the modes where lower is better.
+    pub fn change_str(&self) -> String {
+        let change = self.improvement().abs();
+        if change.is_infinite() {
+            "Inf%".to_string()
+        } else {
+            format!("{:.5}%", change * 100.0)
+        }
+    }
+
+    /// Returns an arrow pointing up if the benchmark got better and down if it got worse,
+    /// whichever way its scores went.
+    pub fn arrow(&self) -> &'static str {
+        match self.status() {
+            "better" => "↑",
+            "worse" => "↓",
+            _ => "",
+        }
+    }
+
+    /// Returns the diff followed by its error, like `+5.00000% ± 1.20000%`.
+    pub fn diff_with_error_str(&self) -> String {
+        if self.diff_error == 0.0 || !self.diff_error.is_finite() {
+            self.diff_str()
+        } else {
+            format!("{} ± {:.5}%", self.diff_str(), self.diff_error * 100.0)
+        }
+    }
+
+    pub fn delta(&self) -> f64 {
+        self.new_score - self.old_score
+    }
+
+    /// Returns how much worse the new score is than the old one in the benchmark's units,
+    /// which is negative if it got better.
+    pub fn absolute_regression(&self) -> f64 {
+        if self.higher_is_better() {
+            -self.delta()
+        } else {
+            self.delta()
+        }
+    }
+
+    pub fn delta_str(&self, format: &NumberFormat) -> String {
+        let sign = if self.delta() < 0.0 { "-" } else { "+" };
+        format!("{sign}{} {}", format.format(self.delta().abs()), self.units)
+    }
+
+    /// Returns the diff in the style of the format, with `ratio` as the relative change.
+    fn styled_diff(&self, format: &NumberFormat, ratio: String) -> String {
+        match format.diff_style {
+            DiffStyle::Ratio => ratio,
+            DiffStyle::Absolute => self.delta_str(format),
+            DiffStyle::Both => format!("{} ({ratio})", self.delta_str(format)),
+        }
+    }
+
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{}",
+            csv_escape(&self.display_name()),
+            self.mode,
+            self.old_score,
+            self.new_score,
+            csv_escape(&self.units),
+            self.diff
+        )
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut json = json!({
+            "name": self.name,
+            "params": params_json(&self.params),
+            "mode": self.mode.to_string(),
+            "old_score": self.old_score,
+            "new_score": self.new_score,
+            "old_count": self.old_count,
+            "new_count": self.new_count,
+            "old_threads": self.old_threads,
+            "new_threads": self.new_threads,
+            "units": self.units,
+            "diff": self.diff,
+            "diff_error": self.diff_error,
+            "significant": self.significant,
+            "high_variance": self.high_variance,
+        });
+        if let Some(p99_diff) = self.p99_diff {
+            json["p99_diff"] = json!(p99_diff);
+        }
+        json
+    }
+
+    fn higher_is_better(&self) -> bool {
+        self.mode.higher_is_better() != self.inverted
+    }
+
+    /// Returns the diff oriented so that a positive value is always an improvement.
+    pub fn improvement(&self) -> f64 {
+        if self.higher_is_better() {
+            self.diff
+        } else {
+            -self.diff
+        }
+    }
+
+    /// Returns the ratio of the new and old scores, oriented so that a value above one is
+    /// always an improvement.
+    pub fn speedup(&self) -> f64 {
+        let ratio = 1.0 + self.diff;
+        if self.higher_is_better() {
+            ratio
+        } else {
+            1.0 / ratio
+        }
+    }
+
+    pub fn is_improvement(&self) -> bool {
+        self.improvement() > 0.0
+    }
+
+    pub fn status(&self) -> &'static str {
+        if self.diff == 0.0 || self.noise {
+            "same"
+        } else if self.is_improvement() {
+            "better"
+        } else {
+            "worse"
+        }
+    }
+
+    /// Returns true if the change is a regression of more than `threshold` percent.
+    pub fn exceeds_threshold(&self, threshold: f64) -> bool {
+        -self.improvement() * 100.0 > threshold
+    }
+}
+
+impl BenchDiff {
+    /// Returns the column names, with the score columns named after the given labels for the
+    /// old and new results.
+    pub fn headers_with_labels(old: &str, new: &str) -> Vec<Cow<'static, str>> {
+        vec![
+            Cow::Owned("name".to_string()),
+            Cow::Owned("mode".to_string()),
+            Cow::Owned(format!("{old} score")),
+            Cow::Owned(format!("{new} score")),
+            Cow::Owned("units".to_string()),
+            Cow::Owned("delta".to_string()),
+            Cow::Owned("diff".to_string()),
+            Cow::Owned("status".to_string()),
+        ]
+    }
+
+    pub fn cells(&self, format: &NumberFormat) -> Vec<Cow<'_, str>> {
+        vec![
+            Cow::Owned(self.display_name()),
+            Cow::Owned(self.mode.to_string()),
+            Cow::Owned(format.format(self.old_score)),
+            Cow::Owned(format.format(self.new_score)),
+            Cow::Owned(self.units.to_string()),
+            Cow::Owned(self.delta_str(format)),
+            if self.noise {
+                Cow::Borrowed("~0%")
+            } else if self.significant {
+                Cow::Owned(self.styled_diff(format, self.diff_with_error_str()))
+            } else {
+                Cow::Owned(format!(
+                    "({})",
+                    self.styled_diff(format, self.diff_with_error_str())
+                ))
+            },
+            if self.high_variance {
+                Cow::Owned(format!("{} (high variance)", self.status()))
+            } else {
+                Cow::Borrowed(self.status())
+            },
+        ]
+    }
+}
+
+impl Tabled for BenchDiff {
+    const LENGTH: usize = 8;
+
+    /// Fields method must return a list of cells.
+    ///
+    /// The cells will be placed in the same row, preserving the order.
+    fn fields(&self) -> Vec<Cow<'_, str>> {
+        self.cells(&NumberFormat::default())
+    }
+    /// Headers must return a list of column names.
+    fn headers() -> Vec<Cow<'static, str>> {
+        Self::headers_with_labels("old", "new")
+    }
+}
+
+impl BenchComparison {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Added(result) | Self::Removed(result) => &result.name,
+            Self::Changed(diff) => &diff.name,
+            Self::UnitMismatch { old, .. } => &old.name,
+        }
+    }
+
+    pub fn display_name(&self) -> String {
+        match self {
+            Self::Added(result) | Self::Removed(result) => result.display_name(),
+            Self::Changed(diff) => diff.display_name(),
+            Self::UnitMismatch { old, .. } => old.display_name(),
+        }
+    }
+
+    pub fn mode(&self) -> Mode {
+        match self {
+            Self::Added(result) | Self::Removed(result) => result.mode,
+            Self::Changed(diff) => diff.mode,
+            Self::UnitMismatch { old, .. } => old.mode,
+        }
+    }
+
+    /// Returns the cell for the p99 diff column, which is only shown if some benchmarks have
+    /// percentiles.
+    pub fn p99_cell(&self) -> Cow<'static, str> {
+        match self.as_changed().and_then(|d| d.p99_diff) {
+            Some(diff) => Cow::Owned(diff_percent(diff)),
+            None => Cow::Borrowed("N/A"),
+        }
+    }
+
+    /// Returns the cells for the old and new iteration count columns.
+    pub fn count_cells(&self) -> [Cow<'static, str>; 2] {
+        let (old, new) = match self {
+            Self::Added(result) => (None, Some(result.count)),
+            Self::Removed(result) => (Some(result.count), None),
+            Self::Changed(diff) => (Some(diff.old_count), Some(diff.new_count)),
+            Self::UnitMismatch { old, new } => (Some(old.count), Some(new.count)),
+        };
+        let cell =
+            |count: Option<i64>| count.map_or(Cow::Borrowed("-"), |c| Cow::Owned(c.to_string()));
+        [cell(old), cell(new)]
+    }
+
+    /// The thread counts of the old and new results, for the columns added by `thread_headers`.
+    pub fn thread_cells(&self) -> [Cow<'static, str>; 2] {
+        let (old, new) = match self {
+            Self::Added(result) => (0, result.threads),
+            Self::Removed(result) => (result.threads, 0),
+            Self::Changed(diff) => (diff.old_threads, diff.new_threads),
+            Self::UnitMismatch { old, new } => (old.threads, new.threads),
+        };
+        [
+            Cow::Owned(format_threads(&old)),
+            Cow::Owned(format_threads(&new)),
+        ]
+    }
+
+    pub fn name_mut(&mut self) -> &mut String {
+        match self {
+            Self::Added(result) | Self::Removed(result) => &mut result.name,
+            Self::Changed(diff) => &mut diff.name,
+            Self::UnitMismatch { old, .. } => &mut old.name,
+        }
+    }
+
+    /// Returns the class the benchmark is in, which is everything before the last `.` of the
+    /// name, leaving out the secondary metric, if any.
+    pub fn class(&self) -> &str {
+        let name = self.name().split(":·").next().unwrap_or_default();
+        name.rsplit_once('.').map_or("", |(class, _)| class)
+    }
+
+    /// Returns true if the benchmark is missing from one of the results.
+    pub fn is_missing(&self) -> bool {
+        matches!(self, Self::Added(_) | Self::Removed(_))
+    }
+
+    /// Returns the diff if the benchmark could be compared.
+    pub fn as_changed(&self) -> Option<&BenchDiff> {
+        match self {
+            Self::Changed(diff) => Some(diff),
+            _ => None,
+        }
+    }
+
+    /// Renders the comparison as a single line like `MyBench.run: 10.0 -> 12.0 ns/op
+    /// (+20.00000%) ↓`.
+    pub fn to_line(&self, format: &NumberFormat) -> String {
+        match self {
+            Self::Added(result) => format!(
+                "{}: added, {} {}",
+                result.display_name(),
+                format.format(result.score),
+                result.units
+            ),
+            Self::Removed(result) => format!(
+                "{}: removed, {} {}",
+                result.display_name(),
+                format.format(result.score),
+                result.units
+            ),
+            Self::Changed(diff) => {
+                let change = match format.diff_style {
+                    _ if diff.noise => "~0%".to_string(),
+                    DiffStyle::Ratio => diff.diff_str(),
+                    DiffStyle::Absolute => diff.delta_str(format),
+                    DiffStyle::Both => format!("{}, {}", diff.delta_str(format), diff.diff_str()),
+                };
+                format!(
+                    "{}: {} -> {} {} ({change}) {}",
+                    diff.display_name(),
+                    format.format(diff.old_score),
+                    format.format(diff.new_score),
+                    diff.units,
+                    diff.arrow()
+                )
+                .trim_end()
+                .to_string()
+            }
+            Self::UnitMismatch { old, new } => format!(
+                "{}: {} {} -> {} {} (units changed)",
+                old.display_name(),
+                format.format(old.score),
+                old.units,
+                format.format(new.score),
+                new.units
+            ),
+        }
+    }
+
+    pub fn to_csv(&self) -> String {
+        match self {
+            Self::Added(result) => format!(
+                "{},{},,{},{},",
+                csv_escape(&result.display_name()),
+                result.mode,
+                result.score,
+                csv_escape(&result.units)
+            ),
+            Self::Removed(result) => format!(
+                "{},{},{},,{},",
+                csv_escape(&result.display_name()),
+                result.mode,
+                result.score,
+                csv_escape(&result.units)
+            ),
+            Self::Changed(diff) => diff.to_csv(),
+            Self::UnitMismatch { old, new } => format!(
+                "{},{},{},{},{},",
+                csv_escape(&old.display_name()),
+                old.mode,
+                old.score,
+                new.score,
+                csv_escape(&format!("{} -> {}", old.units, new.units))
+            ),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        match self {
+            Self::Added(result) => json!({
+                "name": result.name,
+                "params": params_json(&result.params),
+                "mode": result.mode.to_string(),
+                "old_score": null,
+                "new_score": result.score,
+                "units": result.units,
+                "diff": null,
+            }),
+            Self::Removed(result) => json!({
+                "name": result.name,
+                "params": params_json(&result.params),
+                "mode": result.mode.to_string(),
+                "old_score": result.score,
+                "new_score": null,
+                "units": result.units,
+                "diff": null,
+            }),
+            Self::Changed(diff) => diff.to_json(),
+            Self::UnitMismatch { old, new } => json!({
+                "name": old.name,
+                "params": params_json(&old.params),
+                "mode": old.mode.to_string(),
+                "old_score": old.score,
+                "new_score": new.score,
+                "old_units": old.units,
+                "units": new.units,
+                "diff": null,
+            }),
+        }
+    }
+}
+
+impl BenchTrend {
+    pub fn display_name(&self) -> String {
+        display_name(&self.name, &self.params)
+    }
+
+    /// Returns the score of the given run, converted into this trend's units.
+    pub fn score(&self, run: usize) -> Option<f64> {
+        self.runs
+            .get(run)?
+            .as_ref()
+            .map(|r| convert_units(r.score, &r.units, &self.units).unwrap_or(r.score))
+    }
+
+    /// Compares the first run against the last one, if the benchmark is in both.
+    pub fn diff(&self) -> Option<BenchDiff> {
+        let first = self.runs.first()?.as_ref()?;
+        let last = self.runs.last()?.as_ref()?;
+        Some(calculate_delta(last, first))
+    }
+
+    /// Renders the scores as a line of block characters scaled between the lowest and highest
+    /// score, with a blank for each run the benchmark is missing from.
+    pub fn sparkline(&self) -> String {
+        const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+        let scores: Vec<Option<f64>> = (0..self.runs.len()).map(|i| self.score(i)).collect();
+        let (min, max) = scores
+            .iter()
+            .flatten()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &s| {
+                (min.min(s), max.max(s))
+            });
+        scores
+            .iter()
+            .map(|score| match score {
+                Some(s) if max > min => {
+                    let level = (s - min) / (max - min) * (BLOCKS.len() - 1) as f64;
+                    BLOCKS[level.round() as usize]
+                }
+                Some(_) => BLOCKS[BLOCKS.len() / 2 - 1],
+                None => ' ',
+            })
+            .collect()
+    }
+
+    pub fn csv_header(labels: &[String]) -> String {
+        let labels: Vec<Cow<'_, str>> = labels.iter().map(|l| csv_escape(l)).collect();
+        format!("name,mode,units,{},diff", labels.join(","))
+    }
+
+    /// Renders the trend as a single line like `MyBench.run: 10.0 -> N/A -> 12.0 ns/op
+    /// (+20.00000%) ↓`, with the arrows the other way around if `inverted`.
+    pub fn to_line(&self, format: &NumberFormat, inverted: bool) -> String {
+        let scores: Vec<String> = (0..self.runs.len())
+            .map(|i| {
+                self.score(i)
+                    .map_or_else(|| "N/A".to_string(), |s| format.format(s))
+            })
+            .collect();
+        let diff = self.diff().map(|diff| BenchDiff { inverted, ..diff });
+        format!(
+            "{}: {} {} ({}) {}",
+            self.display_name(),
+            scores.join(" -> "),
+            self.units,
+            diff.as_ref()
+                .map_or_else(|| "N/A".to_string(), BenchDiff::diff_str),
+            diff.as_ref().map_or("", BenchDiff::arrow)
+        )
+        .trim_end()
+        .to_string()
+    }
+
+    pub fn to_csv(&self) -> String {
+        let scores: Vec<String> = (0..self.runs.len())
+            .map(|i| self.score(i).map(|s| s.to_string()).unwrap_or_default())
+            .collect();
+        format!(
+            "{},{},{},{},{}",
+            csv_escape(&self.display_name()),
+            self.mode,
+            csv_escape(&self.units),
+            scores.join(","),
+            self.diff().map(|d| d.diff.to_string()).unwrap_or_default()
+        )
+    }
+
+    pub fn to_json(&self) -> Value {
+        let scores: Vec<Option<f64>> = (0..self.runs.len()).map(|i| self.score(i)).collect();
+        json!({
+            "name": self.name,
+            "params": params_json(&self.params),
+            "mode": self.mode.to_string(),
+            "units": self.units,
+            "scores": scores,
+            "diff": self.diff().map(|d| d.diff),
+        })
+    }
+}
+
+/// Returns one row of cells per trend, preceded by a header row with a column per run.
+pub fn trend_rows(
+    trends: &[BenchTrend],
+    labels: &[String],
+    format: &NumberFormat,
+) -> Vec<Vec<String>> {
+    let mut header = vec!["name".to_string(), "mode".to_string(), "units".to_string()];
+    header.extend(labels.iter().cloned());
+    header.push("diff".to_string());
+
+    let mut rows = vec![header];
+    for trend in trends {
+        let mut row = vec![
+            trend.display_name(),
+            trend.mode.to_string(),
+            trend.units.clone(),
+        ];
+        row.extend((0..trend.runs.len()).map(|i| {
+            trend
+                .score(i)
+                .map_or_else(|| "N/A".to_string(), |s| format.format(s))
+        }));
+        row.push(
+            trend
+                .diff()
+                .map_or_else(|| "N/A".to_string(), |d| d.diff_str()),
+        );
+        rows.push(row);
+    }
+    rows
+}
+
+fn markdown_row<'a>(cells: impl IntoIterator<Item = Cow<'a, str>>) -> String {
+    let cells: Vec<String> = cells.into_iter().map(|c| c.replace('|', "\\|")).collect();
+    format!("| {} |", cells.join(" | "))
+}
+
+pub const P99_DIFF_HEADER: &str = "p99 diff";
+
+/// Returns true if any of the comparisons has a p99 diff to show.
+pub fn has_percentiles(comparisons: &[BenchComparison]) -> bool {
+    comparisons
+        .iter()
+        .any(|c| c.as_changed().is_some_and(|d| d.p99_diff.is_some()))
+}
+
+/// Returns the column names for the old and new iteration counts, named after the labels for
+/// the old and new results.
+pub fn count_headers(old: &str, new: &str) -> [Cow<'static, str>; 2] {
+    [
+        Cow::Owned(format!("{old} count")),
+        Cow::Owned(format!("{new} count")),
+    ]
+}
+
+pub fn thread_headers(old: &str, new: &str) -> [Cow<'static, str>; 2] {
+    [
+        Cow::Owned(format!("{old} threads")),
+        Cow::Owned(format!("{new} threads")),
+    ]
+}
+
+/// Renders comparisons as a GitHub flavored Markdown table, with an emoji in the diff column
+/// showing whether each benchmark got better or worse. If `show_counts` or `show_threads`, the
+/// headers should end with the `count_headers` and then the `thread_headers`.
+pub fn to_markdown(
+    comparisons: &[BenchComparison],
+    headers: Vec<Cow<'static, str>>,
+    show_counts: bool,
+    show_threads: bool,
+    format: &NumberFormat,
+) -> String {
+    let mut headers = headers;
+    let diff_column = headers.iter().position(|h| h == "diff");
+    let show_p99 = has_percentiles(comparisons);
+    if show_p99 {
+        headers.push(Cow::Borrowed(P99_DIFF_HEADER));
+    }
+
+    let columns = headers.len();
+    let mut lines = vec![
+        markdown_row(headers),
+        format!("|{}", " --- |".repeat(columns)),
+    ];
+
+    for comparison in comparisons {
+        let mut cells = comparison.cells(format);
+        if show_counts {
+            cells.extend(comparison.count_cells());
+        }
+        if show_threads {
+            cells.extend(comparison.thread_cells());
+        }
+        if show_p99 {
+            cells.push(comparison.p99_cell());
+        }
+        if let (Some(diff), Some(column)) = (comparison.as_changed(), diff_column) {
+            let marker = match diff.status() {
+                "better" => "🟢 ",
+                "worse" => "🔴 ",
+                _ => "",
+            };
+            cells[column] = Cow::Owned(format!("{marker}{}", cells[column]));
+        }
+        lines.push(markdown_row(cells));
+    }
+
+    lines.join("\n")
+}
+
+/// Renders rows of cells as a GitHub flavored Markdown table, the first row being the header.
+pub fn rows_to_markdown(rows: &[Vec<String>]) -> String {
+    let mut lines = vec![];
+    for (i, row) in rows.iter().enumerate() {
+        lines.push(markdown_row(row.iter().map(|c| Cow::Borrowed(c.as_str()))));
+        if i == 0 {
+            lines.push(format!("|{}", " --- |".repeat(row.len())));
+        }
+    }
+    lines.join("\n")
+}
+
+/// Formats a score the way JMH does in text output, with three decimals unless that would
+/// round it to zero.
+fn jmh_score(score: f64) -> String {
+    if score == 0.0 || score.abs() >= 0.001 {
+        format!("{score:.3}")
+    } else {
+        score.to_string()
+    }
+}
+
+impl BenchResult {
+    /// The cells of the result's row in a JMH table with the given parameter columns. The last
+    /// three are the score, the error, which is empty if there isn't one, and the units.
+    fn jmh_cells(&self, param_names: &[&str], show_count: bool) -> Vec<String> {
+        let mut row = vec![self.name.clone()];
+        for name in param_names {
+            let value = self.params.iter().find(|(n, _)| n == name);
+            // JMH prints N/A for parameters that don't apply to the benchmark
+            row.push(value.map_or("N/A".to_string(), |(_, v)| v.clone()));
+        }
+        row.push(self.mode.to_string());
+        if show_count {
+            row.push(self.count.to_string());
+        }
+        row.push(jmh_score(self.score));
+        row.push(if self.error != 0.0 {
+            jmh_score(self.error)
+        } else {
+            String::new()
+        });
+        row.push(self.units.clone());
+        row
+    }
+}
+
+/// Joins the cells of a row of a JMH table, padding each to the width of its column. The name
+/// is aligned to the left and the rest to the right, with a ± before the error if there's an
+/// error column.
+fn jmh_line(row: &[String], widths: &[usize], show_error: bool, header: bool) -> String {
+    let pad = |cell: &str, width: usize, left: bool| {
+        let padding = " ".repeat(width.saturating_sub(cell.chars().count()));
+        if left {
+            format!("{cell}{padding}")
+        } else {
+            format!("{padding}{cell}")
+        }
+    };
+
+    let columns = row.len();
+    let mut line = pad(&row[0], widths[0], true);
+    for column in 1..columns - 2 {
+        line.push_str("  ");
+        line.push_str(&pad(&row[column], widths[column], false));
+    }
+    let error = columns - 2;
+    if show_error {
+        // The ± goes between the score and the error, but not in the header
+        let separator = if header || row[error].is_empty() {
+            "   "
+        } else {
+            " ± "
+        };
+        line.push_str(separator);
+        line.push_str(&pad(&row[error], widths[error], false));
+    }
+    line.push_str("  ");
+    line.push_str(&row[columns - 1]);
+    line.trim_end().to_string()
+}
+
+/// Renders the result as a row of the table JMH prints, like
+/// `MyBench.run  10  avgt  5  1.230 ± 0.010  ns/op` with a column for each of its parameters.
+/// The columns aren't aligned with those of other results, which [`to_jmh_table`] does.
+impl Display for BenchResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let param_names: Vec<&str> = self.params.iter().map(|(name, _)| name.as_str()).collect();
+        let row = self.jmh_cells(&param_names, self.count > 0);
+        let widths: Vec<usize> = row.iter().map(|cell| cell.chars().count()).collect();
+        write!(f, "{}", jmh_line(&row, &widths, self.error != 0.0, false))
+    }
+}
+
+/// Renders the results as the table JMH prints at the end of a run, with the same columns and
+/// alignment, so it can be parsed back into the same results. Each row is laid out like the
+/// [`Display`] of its result, padded to the widths of the columns.
+pub fn to_jmh_table(results: &[BenchResult]) -> String {
+    let mut param_names: Vec<&str> = vec![];
+    for (name, _) in results.iter().flat_map(|r| &r.params) {
+        if !param_names.contains(&name.as_str()) {
+            param_names.push(name);
+        }
+    }
+    let show_count = results.iter().any(|r| r.count > 0);
+    let show_error = results.iter().any(|r| r.error != 0.0);
+
+    let mut header = vec!["Benchmark".to_string()];
+    header.extend(param_names.iter().map(|name| format!("({name})")));
+    header.push("Mode".to_string());
+    if show_count {
+        header.push("Cnt".to_string());
+    }
+    header.extend(["Score", "Error", "Units"].map(str::to_string));
+    let mut rows = vec![header];
+    rows.extend(
+        results
+            .iter()
+            .map(|r| r.jmh_cells(&param_names, show_count)),
+    );
+
+    let widths: Vec<usize> = (0..rows[0].len())
+        .map(|i| rows.iter().map(|r| r[i].chars().count()).max().unwrap_or(0))
+        .collect();
+    rows.iter()
+        .enumerate()
+        .map(|(i, row)| jmh_line(row, &widths, show_error, i == 0))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Escapes the characters that can't appear as-is in XML attribute values.
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn junit_testcase(name: &str, params: &[(String, String)], mode: Mode, body: &str) -> String {
+    // Benchmarks are named like Java methods, so group them by class
+    let (class, method) = name.rsplit_once('.').unwrap_or(("", name));
+    let method = display_name(method, params);
+    format!(
+        "  <testcase classname=\"{}\" name=\"{} ({mode})\">{body}</testcase>",
+        xml_escape(class),
+        xml_escape(&method),
+    )
+}
+
+/// Renders the comparisons as a JUnit XML test suite, where a benchmark fails if it regressed
+/// by more than its threshold.
+pub fn to_junit(comparisons: &[BenchComparison], thresholds: &Thresholds) -> String {
+    let mut failures = 0;
+    let mut skipped = 0;
+    let mut testcases = vec![];
+    for comparison in comparisons {
+        let testcase = match comparison {
+            BenchComparison::Added(result) | BenchComparison::Removed(result) => {
+                skipped += 1;
+                let message = if matches!(comparison, BenchComparison::Added(_)) {
+                    "only in the new results"
+                } else {
+                    "only in the old results"
+                };
+                let body = format!("<skipped message=\"{message}\"/>");
+                junit_testcase(&result.name, &result.params, result.mode, &body)
+            }
+            BenchComparison::Changed(diff) => {
+                let body = if thresholds.exceeded_by(diff) {
+                    failures += 1;
+                    let message = format!(
+                        "old score {} {units}, new score {} {units}, diff {} (threshold {})",
+                        diff.old_score,
+                        diff.new_score,
+                        diff.diff_str(),
+                        thresholds.describe(diff),
+                        units = diff.units,
+                    );
+                    format!("<failure message=\"{}\"/>", xml_escape(&message))
+                } else {
+                    String::new()
+                };
+                junit_testcase(&diff.name, &diff.params, diff.mode, &body)
+            }
+            BenchComparison::UnitMismatch { old, new } => {
+                skipped += 1;
+                let message = format!("units changed from {} to {}", old.units, new.units);
+                let body = format!("<skipped message=\"{}\"/>", xml_escape(&message));
+                junit_testcase(&old.name, &old.params, old.mode, &body)
+            }
+        };
+        testcases.push(testcase);
+    }
+
+    let mut lines = vec![
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#.to_string(),
+        format!(
+            r#"<testsuite name="jmhcmp" tests="{}" failures="{failures}" skipped="{skipped}">"#,
+            testcases.len()
+        ),
+    ];
+    lines.extend(testcases);
+    lines.push("</testsuite>".to_string());
+    lines.join("\n")
+}
+
+/// Escapes the characters that GitHub Actions workflow command messages can't contain.
+fn github_escape(message: &str) -> String {
+    message
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Returns a GitHub Actions workflow command for each significant change, so they show up in
+/// the Actions UI. Regressions of more than their threshold are errors and smaller ones are
+/// warnings.
+pub fn github_annotations<'a>(
+    diffs: impl IntoIterator<Item = &'a BenchDiff>,
+    thresholds: &Thresholds,
+) -> Vec<String> {
+    diffs
+        .into_iter()
+        .filter(|diff| diff.significant && !diff.noise && diff.diff != 0.0)
+        .map(|diff| {
+            let name = diff.display_name();
+            let (command, title, message) = if diff.is_improvement() {
+                let message = format!("{name} improved by {}", diff.change_str());
+                ("notice", "Benchmark improvement", message)
+            } else {
+                let message = format!("{name} regressed by {}", diff.change_str());
+                if thresholds.exceeded_by(diff) {
+                    ("error", "Benchmark regression", message)
+                } else {
+                    ("warning", "Benchmark regression", message)
+                }
+            };
+            format!("::{command} title={title}::{}", github_escape(&message))
+        })
+        .collect()
+}
+
+impl BenchComparison {
+    pub fn cells(&self, format: &NumberFormat) -> Vec<Cow<'_, str>> {
+        match self {
+            Self::Added(result) => vec![
+                Cow::Owned(result.display_name()),
+                Cow::Owned(result.mode.to_string()),
+                Cow::Borrowed("-"),
+                Cow::Owned(format.format(result.score)),
+                Cow::Borrowed(result.units.as_str()),
+                Cow::Borrowed("N/A"),
+                Cow::Borrowed("N/A"),
+                Cow::Borrowed("ADDED"),
+            ],
+            Self::Removed(result) => vec![
+                Cow::Owned(result.display_name()),
+                Cow::Owned(result.mode.to_string()),
+                Cow::Owned(format.format(result.score)),
+                Cow::Borrowed("-"),
+                Cow::Borrowed(result.units.as_str()),
+                Cow::Borrowed("N/A"),
+                Cow::Borrowed("N/A"),
+                Cow::Borrowed("REMOVED"),
+            ],
+            Self::Changed(diff) => diff.cells(format),
+            Self::UnitMismatch { old, new } => vec![
+                Cow::Owned(old.display_name()),
+                Cow::Owned(old.mode.to_string()),
+                Cow::Owned(format.format(old.score)),
+                Cow::Owned(format.format(new.score)),
+                Cow::Borrowed(new.units.as_str()),
+                Cow::Borrowed("N/A"),
+                Cow::Borrowed("N/A"),
+                Cow::Owned(format!("UNIT MISMATCH ({} -> {})", old.units, new.units)),
+            ],
+        }
+    }
+}
+
+impl Tabled for BenchComparison {
+    const LENGTH: usize = BenchDiff::LENGTH;
+
+    fn fields(&self) -> Vec<Cow<'_, str>> {
+        self.cells(&NumberFormat::default())
+    }
+
+    fn headers() -> Vec<Cow<'static, str>> {
+        BenchDiff::headers()
+    }
+}
+
+impl Summary {
+    /// Returns `None` if there are no diffs to summarize.
+    pub fn from_diffs<'a>(diffs: impl IntoIterator<Item = &'a BenchDiff>) -> Option<Summary> {
+        let diffs: Vec<&BenchDiff> = diffs.into_iter().collect();
+        if diffs.is_empty() {
+            return None;
+        }
+
+        let improved = diffs
+            .iter()
+            .filter(|d| !d.noise && d.is_improvement())
+            .count();
+        let regressed = diffs
+            .iter()
+            .filter(|d| !d.noise && d.improvement() < 0.0)
+            .count();
+
+        let mut improvements: Vec<f64> = diffs
+            .iter()
+            .map(|d| d.improvement())
+            .filter(|i| i.is_finite())
+            .collect();
+        improvements.sort_by(f64::total_cmp);
+
+        let mean = improvements.iter().sum::<f64>() / improvements.len() as f64;
+        let mid = improvements.len() / 2;
+        let median = match improvements.len() {
+            0 => f64::NAN,
+            n if n % 2 == 0 => (improvements[mid - 1] + improvements[mid]) / 2.0,
+            _ => improvements[mid],
+        };
+
+        let log_speedups: Vec<f64> = diffs
+            .iter()
+            .map(|d| d.speedup().ln())
+            .filter(|l| l.is_finite())
+            .collect();
+        let geomean = (log_speedups.iter().sum::<f64>() / log_speedups.len() as f64).exp() - 1.0;
+
+        Some(Summary {
+            compared: diffs.len(),
+            improved,
+            regressed,
+            mean,
+            median,
+            geomean,
+            geomean_count: log_speedups.len(),
+        })
+    }
+}
+
+fn percent(value: f64) -> String {
+    if value.is_nan() {
+        "N/A".to_string()
+    } else {
+        // Small negative values round to zero, which shouldn't read as a regression
+        let percent = format!("{:+.2}%", value * 100.0);
+        if percent == "-0.00%" {
+            "+0.00%".to_string()
+        } else {
+            percent
+        }
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "{} benchmarks compared, {} improved, {} regressed, mean improvement {}, median improvement {}",
+            self.compared,
+            self.improved,
+            self.regressed,
+            percent(self.mean),
+            percent(self.median)
+        )?;
+        write!(
+            f,
+            "\ngeomean improvement {} over {} benchmarks",
+            percent(self.geomean),
+            self.geomean_count
+        )
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Line(line) => write!(f, "line {line}"),
+            Self::Entry(index) => write!(f, "benchmark {index}"),
+            Self::Row => write!(f, "row"),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::InvalidMode { at, token } => write!(f, "{at}: invalid mode '{token}'"),
+            Self::InvalidFloat { at, token } => write!(f, "{at}: invalid number '{token}'"),
+            Self::InvalidInt { at, token } => write!(f, "{at}: invalid integer '{token}'"),
+            Self::MissingName { at } => write!(f, "{at}: missing benchmark name"),
+            Self::MissingField { at, field } => write!(f, "{at}: missing {field}"),
+            Self::InvalidJson { message } => write!(f, "invalid JSON: {message}"),
+            Self::MissingMetric { at } => write!(f, "{at}: missing primaryMetric"),
+            Self::DuplicateBenchmark { at, name, mode } => {
+                write!(f, "{at}: duplicate result for {name} ({mode})")
+            }
+            Self::NonFiniteScore { at, token } => write!(f, "{at}: non-finite score '{token}'"),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+impl Diagnostics {
+    pub fn add(&mut self, path: impl Into<String>, errors: Vec<ParseError>) {
+        self.files.push((path.into(), errors));
+    }
+
+    /// Returns how many rows couldn't be parsed in all the files.
+    pub fn count(&self) -> usize {
+        self.files.iter().map(|(_, errors)| errors.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count() == 0
+    }
+}
+
+/// Renders the errors of each file that has any under a line with the file's path, like
+/// `old.txt: skipped 1 row(s)`.
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let files = self.files.iter().filter(|(_, errors)| !errors.is_empty());
+        for (i, (path, errors)) in files.enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{path}: skipped {} row(s)", errors.len())?;
+            for e in errors {
+                write!(f, "\n  {e}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Mode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            // The short labels JMH prints, and the names of its Mode enum
+            "thrpt" | "throughput" => Ok(Self::Throughput),
+            "avgt" | "average" | "averagetime" => Ok(Self::AverageTime),
+            "sample" | "sampletime" => Ok(Self::SampleTime),
+            "ss" | "singleshot" | "singleshottime" => Ok(Self::SingleShotTime),
+            _ => Err(format!("unknown mode '{s}'")),
+        }
+    }
+}
+
+impl Display for Mode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::SampleTime => write!(f, "sample"),
+            Self::Throughput => write!(f, "thrpt"),
+            Self::SingleShotTime => write!(f, "ss"),
+            Self::AverageTime => write!(f, "avgt"),
+        }
+    }
+}
+
+impl Display for Statistic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Mean => write!(f, "mean"),
+            Self::Median => write!(f, "median"),
+            Self::Min => write!(f, "min"),
+            Self::Max => write!(f, "max"),
+        }
+    }
+}
+
+impl Display for JmhVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let parts: Vec<String> = self.0.iter().map(u32::to_string).collect();
+        write!(f, "{}", parts.join("."))
+    }
+}
+
+impl Statistic {
+    /// Computes the statistic of the samples, or returns `None` if there aren't any.
+    pub fn of(&self, samples: &[f64]) -> Option<f64> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let middle = sorted.len() / 2;
+        Some(match self {
+            Self::Mean => sorted.iter().sum::<f64>() / sorted.len() as f64,
+            Self::Median if sorted.len().is_multiple_of(2) => {
+                (sorted[middle - 1] + sorted[middle]) / 2.0
+            }
+            Self::Median => sorted[middle],
+            Self::Min => sorted[0],
+            Self::Max => sorted[sorted.len() - 1],
+        })
+    }
+}
+
+impl JmhVersion {
+    /// Parses the numeric parts of a version, ignoring suffixes like `-SNAPSHOT`.
+    fn parse(version: &str) -> Option<JmhVersion> {
+        let parts: Vec<u32> = version
+            .split('.')
+            .map_while(|part| {
+                let digits: String = part.chars().take_while(char::is_ascii_digit).collect();
+                digits.parse().ok()
+            })
+            .collect();
+        (!parts.is_empty()).then_some(JmhVersion(parts))
+    }
+}
+
+/// Finds the version of JMH that produced the output in a reader, from the `# JMH version`
+/// line of text output or the `jmhVersion` field of JSON output.
+pub fn read_jmh_version<R: Read>(reader: R) -> Result<Option<JmhVersion>, std::io::Error> {
+    let mut reader = BufReader::new(reader);
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        find_jmh_version(BufReader::new(GzDecoder::new(reader)))
+    } else {
+        find_jmh_version(reader)
+    }
+}
+
+fn find_jmh_version<R: BufRead>(reader: R) -> Result<Option<JmhVersion>, std::io::Error> {
+    let pattern = Regex::new(r#"^# JMH version: (\S+)|"jmhVersion"\s*:\s*"([^"]+)""#)
+        .expect("the version pattern is valid");
+    for line in reader.lines() {
+        let line = line?;
+        if let Some(captures) = pattern.captures(line.trim_start()) {
+            let version = captures.get(1).or_else(|| captures.get(2));
+            if let Some(version) = version.and_then(|v| JmhVersion::parse(v.as_str())) {
+                return Ok(Some(version));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// The JVM a run was on, which can change the scores as much as the code being benchmarked.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunEnvironment {
+    pub jmh_version: Option<JmhVersion>,
+    /// Like `JDK 17.0.2, OpenJDK 64-Bit Server VM, 17.0.2+8`.
+    pub vm: Option<String>,
+    /// The path of the `java` binary.
+    pub invoker: Option<String>,
+    pub options: Vec<String>,
+}
+
+impl RunEnvironment {
+    fn is_empty(&self) -> bool {
+        self.jmh_version.is_none()
+            && self.vm.is_none()
+            && self.invoker.is_none()
+            && self.options.is_empty()
+    }
+}
+
+/// Finds the JMH version and JVM of the run that produced the output in a reader, from the
+/// `# JMH version`, `# VM version`, `# VM invoker` and `# VM options` lines of text output or
+/// the `jmhVersion`, `jdkVersion`, `vmName`, `vmVersion`, `jvm` and `jvmArgs` fields of the
+/// first benchmark of JSON output.
+///
+/// The lines are read one at a time and only up to the first benchmark, since every benchmark
+/// repeats them.
+pub fn read_environment<R: Read>(reader: R) -> Result<Option<RunEnvironment>, std::io::Error> {
+    let mut reader = BufReader::new(reader);
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        find_environment(BufReader::new(GzDecoder::new(reader)))
+    } else {
+        find_environment(reader)
+    }
+}
+
+fn find_environment<R: BufRead>(reader: R) -> Result<Option<RunEnvironment>, std::io::Error> {
+    let field =
+        Regex::new(r#""(\w+)"\s*:\s*("(?:[^"\\]|\\.)*")"#).expect("the field pattern is valid");
+    let mut environment = RunEnvironment::default();
+    let string = Regex::new(r#""(?:[^"\\]|\\.)*""#).expect("the string pattern is valid");
+    let mut fields: HashMap<String, String> = HashMap::new();
+    let mut depth = 0;
+    // The text of a `jvmArgs` array that spans several lines, while it is being read
+    let mut jvm_args: Option<String> = None;
+
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if let Some(args) = &mut jvm_args {
+            args.push_str(line);
+            if let Some(options) = json_strings(args) {
+                environment.options = options;
+                jvm_args = None;
+            }
+            continue;
+        }
+
+        if let Some(version) = line.strip_prefix("# JMH version: ") {
+            environment.jmh_version = JmhVersion::parse(version);
+        } else if let Some(vm) = line.strip_prefix("# VM version: ") {
+            environment.vm = Some(vm.to_string());
+        } else if let Some(invoker) = line.strip_prefix("# VM invoker: ") {
+            environment.invoker = Some(invoker.to_string());
+        } else if let Some(options) = line.strip_prefix("# VM options: ") {
+            if options != "<none>" {
+                environment.options = options.split_whitespace().map(str::to_string).collect();
+            }
+        } else if line.starts_with("# Benchmark: ") || is_table_header(line) {
+            break;
+        } else {
+            for captures in field.captures_iter(line) {
+                if let Ok(value) = serde_json::from_str::<String>(&captures[2]) {
+                    fields.entry(captures[1].to_string()).or_insert(value);
+                }
+            }
+            if let Some((_, args)) = line.split_once("\"jvmArgs\"") {
+                let args = args.trim_start().trim_start_matches(':').trim_start();
+                match json_strings(args) {
+                    Some(options) => environment.options = options,
+                    None if args.starts_with('[') => jvm_args = Some(args.to_string()),
+                    None => {}
+                }
+            }
+            // Stop at the end of the first benchmark, the braces in strings aside
+            let unquoted = string.replace_all(line, "");
+            for c in unquoted.chars() {
+                match c {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+            }
+            if unquoted.contains('}') && depth == 0 {
+                break;
+            }
+        }
+    }
+
+    let field = |name| fields.get(name).map(String::as_str);
+    if let Some(version) = field("jmhVersion") {
+        environment.jmh_version = JmhVersion::parse(version);
+    }
+    // Written the way text output has it, so that text and JSON files can be compared
+    match (field("jdkVersion"), field("vmName"), field("vmVersion")) {
+        (Some(jdk), Some(name), Some(version)) => {
+            environment.vm = Some(format!("JDK {jdk}, {name}, {version}"));
+        }
+        (Some(jdk), _, _) => environment.vm = Some(format!("JDK {jdk}")),
+        _ => {}
+    }
+    if let Some(jvm) = field("jvm") {
+        environment.invoker = Some(jvm.to_string());
+    }
+    Ok(Some(environment).filter(|e| !e.is_empty()))
+}
+
+/// Parses the JSON array of strings at the start of `input`, or returns `None` if it isn't
+/// complete yet.
+fn json_strings(input: &str) -> Option<Vec<String>> {
+    serde_json::Deserializer::from_str(input)
+        .into_iter::<Vec<String>>()
+        .next()?
+        .ok()
+}
+
+/// Parses a single row without parameter columns, like `MyBench.run avgt 5 1.23 ± 0.01 ns/op`.
+///
+/// Any input is either parsed or rejected with an error, so rows that are cut short or mangled
+/// by JMH plugins can't make it panic.
+pub fn parse_row(input: &str, line: usize) -> Result<BenchResult, ParseError> {
+    parse_row_with_params(input, line, &[])
+}
+
+/// Parses a single row without parameter columns, like `MyBench.run avgt 5 1.23 ± 0.01 ns/op`.
+/// Errors are located at [`Location::Row`], since the row isn't part of a file.
+///
+/// ```
+/// use jmhcmp::{BenchResult, Mode};
+///
+/// let result: BenchResult = "com.example.Bench.alpha thrpt 5 1000 ± 10 ops/s".parse().unwrap();
+/// assert_eq!(result.name, "com.example.Bench.alpha");
+/// assert_eq!(result.mode, Mode::Throughput);
+/// assert_eq!(result.score, 1000.0);
+/// assert_eq!(result.error, 10.0);
+/// assert_eq!(result.units, "ops/s");
+///
+/// let error = "MyBench.run fast 5 1.0 ns/op".parse::<BenchResult>().unwrap_err();
+/// assert_eq!(error.to_string(), "row: invalid mode 'fast'");
+/// ```
+impl FromStr for BenchResult {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_text_row(s, Location::Row, &[], DecimalSeparator::Point)
+    }
+}
+
+/// Parses a row of a table that has a column for each of the given `@Param` columns between
+/// the benchmark name and the mode.
+pub fn parse_row_with_params(
+    input: &str,
+    line: usize,
+    param_columns: &[ParamColumn],
+) -> Result<BenchResult, ParseError> {
+    parse_text_row(
+        input,
+        Location::Line(line),
+        param_columns,
+        DecimalSeparator::Point,
+    )
+}
+
+fn parse_text_row(
+    input: &str,
+    at: Location,
+    param_columns: &[ParamColumn],
+    separator: DecimalSeparator,
+) -> Result<BenchResult, ParseError> {
+    let missing = |field| ParseError::MissingField { at, field };
+    let invalid_float = |token: &str| ParseError::InvalidFloat {
+        at,
+        token: token.to_string(),
+    };
+
+    let (name, values, mut parts) = match split_param_columns(input, param_columns) {
+        Some((name, values, rest)) => (name, values, rest.split_whitespace().fuse().peekable()),
+        // The row isn't aligned with the header, so fall back to splitting on whitespace
+        None => {
+            let mut parts = input.split_whitespace().fuse().peekable();
+            let name = parts.next().ok_or(ParseError::MissingName { at })?;
+            let mut values = vec![];
+            for _ in param_columns {
+                values.push(parts.next().ok_or(missing("parameter"))?);
+            }
+            (name, values, parts)
+        }
+    };
+    let name = name.to_string();
+
+    let params = param_columns
+        .iter()
+        .zip(values)
+        // JMH prints N/A for parameters that don't apply to this benchmark
+        .filter(|&(_, value)| value != "N/A")
+        .map(|(column, value)| (column.name.clone(), value.to_string()))
+        .collect();
+
+    let mode = parts.next().ok_or(missing("mode"))?;
+    let mode = mode.parse::<Mode>().map_err(|_| ParseError::InvalidMode {
+        at,
+        token: mode.to_string(),
+    })?;
+
+    // Some configurations leave out the Cnt column, in which case this is the score. An
+    // integer is only a count if it's followed by the score.
+    let token = parts.next().ok_or(missing("score"))?;
+    let followed_by_score = parts
+        .peek()
+        .is_some_and(|t| parse_score(t, separator).is_some());
+    let (count, token) = match token.parse::<i64>() {
+        Ok(count) if followed_by_score => (count, parts.next().unwrap_or_default()),
+        _ => (0, token),
+    };
+
+    let score = parse_score(token, separator).ok_or_else(|| invalid_float(token))?;
+    if !score.is_finite() {
+        return Err(ParseError::NonFiniteScore {
+            at,
+            token: token.to_string(),
+        });
+    }
+
+    // Single iteration runs have no error column
+    let error = if parts.next_if_eq(&"±").is_some() {
+        let error = parts.next().ok_or(missing("error"))?;
+        let error = parse_score(error, separator).ok_or_else(|| invalid_float(error))?;
+        // A failed error estimate shouldn't make every diff look significant
+        if error.is_finite() {
+            error
+        } else {
+            0.0
+        }
+    } else {
+        0.0
+    };
+
+    let units = parts.next().ok_or(missing("units"))?.to_string();
+
+    Ok(BenchResult {
+        name,
+        params,
+        mode,
+        threads: 0,
+        count,
+        score,
+        error,
+        units,
+        percentiles: None,
+        secondary_metrics: vec![],
+        raw_data: vec![],
+        warmup_scores: vec![],
+    })
+}
+
+/// Parses a score, which JMH can print as `NaN` or `∞` if the measurement failed.
+fn parse_score(token: &str, separator: DecimalSeparator) -> Option<f64> {
+    let (decimal, thousands) = match separator {
+        DecimalSeparator::Point => ('.', ','),
+        DecimalSeparator::Comma => (',', '.'),
+    };
+    match token {
+        "∞" | "+∞" => Some(f64::INFINITY),
+        "-∞" => Some(f64::NEG_INFINITY),
+        _ if token.contains(thousands) => strip_thousands(token, decimal, thousands)?.parse().ok(),
+        _ => token.replace(decimal, ".").parse().ok(),
+    }
+}
+
+/// Removes the separators between groups of thousands from a number and turns its decimal
+/// point into a `.`, or returns `None` if the groups aren't three digits long, since then the
+/// separator is more likely a decimal point in another locale.
+fn strip_thousands(token: &str, decimal: char, thousands: char) -> Option<String> {
+    let unsigned = token.trim_start_matches(['+', '-']);
+    let sign = &token[..token.len() - unsigned.len()];
+    let (integer, fraction) = match unsigned.split_once(decimal) {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (unsigned, None),
+    };
+
+    let groups: Vec<&str> = integer.split(thousands).collect();
+    let is_digits = |group: &str| group.chars().all(|c| c.is_ascii_digit());
+    let valid = (1..=3).contains(&groups[0].len())
+        && groups.iter().all(|g| is_digits(g))
+        && groups[1..].iter().all(|g| g.len() == 3);
+    if !valid {
+        return None;
+    }
+
+    let mut number = format!("{sign}{}", groups.concat());
+    if let Some(fraction) = fraction {
+        number.push('.');
+        number.push_str(fraction);
+    }
+    Some(number)
+}
+
+/// A `(param)` column in the header of a results table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParamColumn {
+    pub name: String,
+    /// The offset just past the end of the column, since JMH right-aligns parameter values.
+    pub end: usize,
+}
+
+/// Returns the `(param)` columns in a table header.
+fn param_columns(header: &str) -> Vec<ParamColumn> {
+    let mut columns = vec![];
+    let mut offset = 0;
+    for piece in header.split_inclusive(char::is_whitespace) {
+        let token = piece.trim_end();
+        if let Some(name) = token.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+            columns.push(ParamColumn {
+                name: name.to_string(),
+                end: offset + token.len(),
+            });
+        }
+        offset += piece.len();
+    }
+    columns
+}
+
+/// Splits a row into its name, the values of the parameter columns and the remaining columns,
+/// using the offsets of the columns in the header so that values can contain spaces.
+///
+/// Returns `None` if the row isn't aligned with the header, including when it's too short or a
+/// column offset falls inside a multibyte character.
+fn split_param_columns<'a>(
+    row: &'a str,
+    columns: &[ParamColumn],
+) -> Option<(&'a str, Vec<&'a str>, &'a str)> {
+    let mut cells = vec![];
+    let mut start = 0;
+    for column in columns {
+        cells.push(row.get(start..column.end)?);
+        // Parameter values are right-aligned, so an aligned column is followed by a gap
+        if !row.get(column.end..)?.starts_with(char::is_whitespace) {
+            return None;
+        }
+        start = column.end;
+    }
+
+    // The name and the first value share the cell, since the name is left-aligned
+    let (name, first) = cells.first()?.trim().split_once(char::is_whitespace)?;
+    cells[0] = first;
+
+    // JMH separates columns with at least two spaces, and the mode comes right after the
+    // parameters
+    let values: Vec<&str> = cells.into_iter().map(str::trim).collect();
+    let rest = row.get(start..)?;
+    let aligned = values.iter().all(|v| !v.is_empty() && !v.contains("  "))
+        && rest.split_whitespace().next()?.parse::<Mode>().is_ok();
+    aligned.then_some((name, values, rest))
+}
+
+/// Splits parsed rows into results and errors.
+///
+/// Only the first result for each benchmark is kept, so that it's clear which one gets
+/// compared. Later ones are reported as errors.
+fn collect_rows(
+    rows: impl Iterator<Item = (Location, Result<BenchResult, ParseError>)>,
+) -> (Vec<BenchResult>, Vec<ParseError>) {
+    let mut results: Vec<BenchResult> = vec![];
+    let mut errors = vec![];
+    for (at, row) in rows {
+        match row {
+            Ok(r) if results.iter().any(|o| o.same_key(&r)) => {
+                errors.push(ParseError::DuplicateBenchmark {
+                    at,
+                    name: r.display_name(),
+                    mode: r.mode,
+                })
+            }
+            Ok(r) => results.push(r),
+            Err(e) => errors.push(e),
+        }
+    }
+    (results, errors)
+}
+
+/// Parses the rows of a table, numbering lines from `first_line`. The first line is skipped
+/// if it's a header, and blank lines are ignored.
+fn parse_lines(
+    input: &str,
+    first_line: usize,
+    separator: DecimalSeparator,
+) -> impl Iterator<Item = (Location, Result<BenchResult, ParseError>)> + '_ {
+    // Unlike splitting on newlines, this also drops the carriage returns of Windows line
+    // endings, so they don't end up in the units or the header offsets
+    let lines: Vec<&str> = input.lines().collect();
+    let header = lines
+        .iter()
+        .position(|l| !l.trim().is_empty())
+        .filter(|&i| is_table_header(lines[i]));
+    let param_names = header.map(|i| param_columns(lines[i])).unwrap_or_default();
+    lines
+        .into_iter()
+        .enumerate()
+        .filter(move |&(i, s)| Some(i) != header && !s.trim().is_empty())
+        .map(move |(i, s)| {
+            let at = Location::Line(first_line + i);
+            (at, parse_text_row(s, at, &param_names, separator))
+        })
+}
+
+pub fn parse_block(input: &str) -> (Vec<BenchResult>, Vec<ParseError>) {
+    collect_rows(parse_lines(input, 1, DecimalSeparator::Point))
+}
+
+fn json_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.parse::<f64>().ok(),
+        _ => None,
+    }
+}
+
+fn parse_json_entry(entry: &Value, index: usize) -> Result<BenchResult, ParseError> {
+    let at = Location::Entry(index);
+    let missing = |field| ParseError::MissingField { at, field };
+
+    let name = entry
+        .get("benchmark")
+        .and_then(Value::as_str)
+        .ok_or(ParseError::MissingName { at })?
+        .to_string();
+
+    let params = match entry.get("params") {
+        Some(Value::Object(params)) => params
+            .iter()
+            .map(|(name, value)| {
+                let value = value
+                    .as_str()
+                    .map_or_else(|| value.to_string(), str::to_string);
+                (name.clone(), value)
+            })
+            .collect(),
+        _ => vec![],
+    };
+
+    let mode = entry
+        .get("mode")
+        .and_then(Value::as_str)
+        .ok_or(missing("mode"))?;
+    let mode = mode.parse::<Mode>().map_err(|_| ParseError::InvalidMode {
+        at,
+        token: mode.to_string(),
+    })?;
+
+    let count = entry
+        .get("measurementIterations")
+        .ok_or(missing("measurementIterations"))?;
+    let count = count.as_i64().ok_or_else(|| ParseError::InvalidInt {
+        at,
+        token: count.to_string(),
+    })?;
+
+    let metric = entry
+        .get("primaryMetric")
+        .ok_or(ParseError::MissingMetric { at })?;
+
+    let score = metric.get("score").ok_or(missing("score"))?;
+    let token = score;
+    let score = json_f64(score).ok_or_else(|| ParseError::InvalidFloat {
+        at,
+        token: token.to_string(),
+    })?;
+    if !score.is_finite() {
+        return Err(ParseError::NonFiniteScore {
+            at,
+            token: token.to_string(),
+        });
+    }
+
+    // JMH writes "NaN" for the error of single-iteration runs
+    let error = metric
+        .get("scoreError")
+        .and_then(json_f64)
+        .filter(|e| e.is_finite())
+        .unwrap_or(0.0);
+
+    let threads = entry.get("threads").and_then(Value::as_i64).unwrap_or(0);
+
+    let units = metric
+        .get("scoreUnit")
+        .and_then(Value::as_str)
+        .ok_or(missing("scoreUnit"))?
+        .to_string();
+
+    let percentiles = match mode {
+        Mode::SampleTime => metric.get("scorePercentiles").and_then(json_percentiles),
+        _ => None,
+    };
+
+    // One array of iteration scores per fork
+    let raw_data = match metric.get("rawData") {
+        Some(Value::Array(forks)) => forks
+            .iter()
+            .filter_map(Value::as_array)
+            .flatten()
+            .filter_map(json_f64)
+            .filter(|s| s.is_finite())
+            .collect(),
+        _ => vec![],
+    };
+
+    let secondary_metrics = match entry.get("secondaryMetrics") {
+        Some(Value::Object(metrics)) => metrics
+            .iter()
+            .filter_map(|(name, metric)| {
+                let metric = SecondaryMetric {
+                    score: metric
+                        .get("score")
+                        .and_then(json_f64)
+                        .filter(|s| s.is_finite())?,
+                    error: metric
+                        .get("scoreError")
+                        .and_then(json_f64)
+                        .filter(|e| e.is_finite())
+                        .unwrap_or(0.0),
+                    units: metric.get("scoreUnit")?.as_str()?.to_string(),
+                };
+                // The names of profiler metrics start with a middle dot
+                Some((name.trim_start_matches('·').to_string(), metric))
+            })
+            .collect(),
+        _ => vec![],
+    };
+
+    Ok(BenchResult {
+        name,
+        params,
+        mode,
+        threads,
+        count,
+        score,
+        error,
+        units,
+        percentiles,
+        secondary_metrics,
+        raw_data,
+        warmup_scores: vec![],
+    })
+}
+
+fn json_percentiles(percentiles: &Value) -> Option<Percentiles> {
+    let percentile = |key| percentiles.get(key).and_then(json_f64);
+    Some(Percentiles {
+        p50: percentile("50.0")?,
+        p90: percentile("90.0")?,
+        p99: percentile("99.0")?,
+        p999: percentile("99.9")?,
+    })
+}
+
+pub fn parse_json(input: &str) -> (Vec<BenchResult>, Vec<ParseError>) {
+    parse_json_value(serde_json::from_str(input))
+}
+
+/// Parses JMH JSON output from a reader, which only fails if reading does.
+pub fn parse_json_reader<R: Read>(
+    reader: R,
+) -> Result<(Vec<BenchResult>, Vec<ParseError>), std::io::Error> {
+    match serde_json::from_reader(BufReader::new(reader)) {
+        Err(e) if e.is_io() => Err(e.into()),
+        value => Ok(parse_json_value(value)),
+    }
+}
+
+fn parse_json_value(
+    value: Result<Value, serde_json::Error>,
+) -> (Vec<BenchResult>, Vec<ParseError>) {
+    let entries = match value {
+        Ok(Value::Array(entries)) => entries,
+        Ok(entry @ Value::Object(_)) => vec![entry],
+        Ok(_) => {
+            let message = "expected an array of benchmarks".to_string();
+            return (vec![], vec![ParseError::InvalidJson { message }]);
+        }
+        Err(e) => {
+            let message = e.to_string();
+            return (vec![], vec![ParseError::InvalidJson { message }]);
+        }
+    };
+
+    collect_rows(entries.iter().enumerate().map(|(i, entry)| {
+        let index = i + 1;
+        (Location::Entry(index), parse_json_entry(entry, index))
+    }))
+}
+
+pub fn parse_json_file<P: AsRef<Path>>(
+    path: P,
+) -> Result<(Vec<BenchResult>, Vec<ParseError>), std::io::Error> {
+    parse_json_reader(File::open(path)?)
+}
+
+fn is_json_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+/// Splits a row of CSV into its fields, unquoting the ones that are quoted as described in
+/// RFC 4180.
+fn split_csv_row(row: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = row.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// The columns of JMH CSV output, by their position in the header.
+struct CsvColumns {
+    name: usize,
+    mode: usize,
+    /// Older versions of JMH don't write the thread count.
+    threads: Option<usize>,
+    count: usize,
+    score: usize,
+    error: usize,
+    units: usize,
+    /// The `Param: name` columns.
+    params: Vec<(usize, String)>,
+}
+
+impl CsvColumns {
+    fn from_header(header: &[String]) -> Result<Self, ParseError> {
+        let at = Location::Line(1);
+        let column = |name: &'static str| {
+            header
+                .iter()
+                .position(|h| h == name)
+                .ok_or(ParseError::MissingField { at, field: name })
+        };
+        Ok(CsvColumns {
+            name: column("Benchmark")?,
+            mode: column("Mode")?,
+            threads: column("Threads").ok(),
+            count: column("Samples")?,
+            score: column("Score")?,
+            // The header has the confidence level in it, like "Score Error (99.9%)"
+            error: header
+                .iter()
+                .position(|h| h.starts_with("Score Error"))
+                .ok_or(ParseError::MissingField {
+                    at,
+                    field: "Score Error",
+                })?,
+            units: column("Unit")?,
+            params: header
+                .iter()
+                .enumerate()
+                .filter_map(|(i, h)| Some((i, h.strip_prefix("Param: ")?.to_string())))
+                .collect(),
+        })
+    }
+}
+
+fn parse_csv_row(
+    fields: &[String],
+    columns: &CsvColumns,
+    line: usize,
+    separator: DecimalSeparator,
+) -> Result<BenchResult, ParseError> {
+    let at = Location::Line(line);
+    let field = |i: usize, name: &'static str| {
+        fields
+            .get(i)
+            .map(String::as_str)
+            .ok_or(ParseError::MissingField { at, field: name })
+    };
+
+    let name = field(columns.name, "name")?;
+    if name.is_empty() {
+        return Err(ParseError::MissingName { at });
+    }
+
+    let params = columns
+        .params
+        .iter()
+        // Parameters that don't apply to the benchmark are left empty
+        .filter_map(|(i, name)| {
+            let value = fields.get(*i).filter(|v| !v.is_empty() && *v != "N/A")?;
+            Some((name.clone(), value.clone()))
+        })
+        .collect();
+
+    let mode = field(columns.mode, "mode")?;
+    let mode = mode.parse::<Mode>().map_err(|_| ParseError::InvalidMode {
+        at,
+        token: mode.to_string(),
+    })?;
+
+    let threads = match columns.threads {
+        Some(column) => {
+            let threads = field(column, "threads")?;
+            threads.parse::<i64>().map_err(|_| ParseError::InvalidInt {
+                at,
+                token: threads.to_string(),
+            })?
+        }
+        None => 0,
+    };
+
+    let count = field(columns.count, "samples")?;
+    let count = count.parse::<i64>().map_err(|_| ParseError::InvalidInt {
+        at,
+        token: count.to_string(),
+    })?;
+
+    let token = field(columns.score, "score")?;
+    let score = parse_score(token, separator).ok_or_else(|| ParseError::InvalidFloat {
+        at,
+        token: token.to_string(),
+    })?;
+    if !score.is_finite() {
+        return Err(ParseError::NonFiniteScore {
+            at,
+            token: token.to_string(),
+        });
+    }
+
+    // Single iteration runs have a NaN error
+    let error = field(columns.error, "error")?;
+    let error = parse_score(error, separator)
+        .filter(|e| e.is_finite())
+        .unwrap_or(0.0);
+
+    let units = field(columns.units, "units")?.to_string();
+
+    Ok(BenchResult {
+        name: name.to_string(),
+        params,
+        mode,
+        threads,
+        count,
+        score,
+        error,
+        units,
+        percentiles: None,
+        secondary_metrics: vec![],
+        raw_data: vec![],
+        warmup_scores: vec![],
+    })
+}
+
+/// Parses JMH CSV output, as written with `-rf csv`.
+pub fn parse_csv(input: &str) -> (Vec<BenchResult>, Vec<ParseError>) {
+    parse_csv_reader(input.as_bytes()).expect("reading from a string can't fail")
+}
+
+/// Parses JMH CSV output from a reader, which only fails if reading does.
+pub fn parse_csv_reader<R: Read>(
+    reader: R,
+) -> Result<(Vec<BenchResult>, Vec<ParseError>), std::io::Error> {
+    parse_csv_with(reader, DecimalSeparator::Point)
+}
+
+fn parse_csv_with<R: Read>(
+    reader: R,
+    separator: DecimalSeparator,
+) -> Result<(Vec<BenchResult>, Vec<ParseError>), std::io::Error> {
+    let mut lines = BufReader::new(reader).lines();
+    let header = match lines.next().transpose()? {
+        Some(header) => split_csv_row(&header),
+        None => vec![],
+    };
+    let columns = match CsvColumns::from_header(&header) {
+        Ok(columns) => columns,
+        Err(e) => return Ok((vec![], vec![e])),
+    };
+
+    let mut rows = vec![];
+    for (i, line) in lines.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // The header is the first line
+        let number = i + 2;
+        let row = parse_csv_row(&split_csv_row(&line), &columns, number, separator);
+        rows.push((Location::Line(number), row));
+    }
+    Ok(collect_rows(rows.into_iter()))
+}
+
+fn is_csv_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+}
+
+/// Returns true if the line is the header JMH starts CSV output with.
+fn is_csv_header(line: &str) -> bool {
+    line.trim_start().starts_with("\"Benchmark\",")
+}
+
+/// Parses benchmark results, detecting whether they are JMH JSON, CSV or a text table.
+pub fn parse_str(input: &str) -> (Vec<BenchResult>, Vec<ParseError>) {
+    parse_reader(input.as_bytes()).expect("reading from a string can't fail")
+}
+
+/// The bytes every gzip file starts with.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+fn is_table_header(line: &str) -> bool {
+    line.trim_start()
+        .get(.."Benchmark".len())
+        .is_some_and(|start| start.eq_ignore_ascii_case("Benchmark"))
+}
+
+/// Parses benchmark results from a reader, detecting whether they are JMH JSON, CSV or a
+/// text table.
+///
+/// Text output is read a line at a time, so only the current block is held in memory.
+/// Gzip compressed input is decompressed first.
+///
+/// This takes any `Read` rather than only a `BufRead`, since it's buffered here anyway, so
+/// files, stdin, HTTP responses and an `io::Cursor` over a string can all be passed
+/// directly. The only errors are from reading, rows that can't be parsed are returned
+/// alongside the results.
+pub fn parse_reader<R: Read>(
+    reader: R,
+) -> Result<(Vec<BenchResult>, Vec<ParseError>), std::io::Error> {
+    parse_reader_with(reader, DecimalSeparator::Point)
+}
+
+/// Like [`parse_reader`], reading the scores of text and CSV output with the given decimal
+/// separator. JSON always uses a point.
+pub fn parse_reader_with<R: Read>(
+    reader: R,
+    separator: DecimalSeparator,
+) -> Result<(Vec<BenchResult>, Vec<ParseError>), std::io::Error> {
+    let mut reader = BufReader::new(reader);
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        parse_decompressed(BufReader::new(GzDecoder::new(reader)), separator)
+    } else {
+        parse_decompressed(reader, separator)
+    }
+}
+
+fn parse_decompressed<R: BufRead>(
+    mut reader: R,
+    separator: DecimalSeparator,
+) -> Result<(Vec<BenchResult>, Vec<ParseError>), std::io::Error> {
+    let mut line = String::new();
+    let mut line_number = 0;
+    while reader.read_line(&mut line)? > 0 {
+        line_number += 1;
+        if !line.trim().is_empty() {
+            break;
+        }
+        line.clear();
+    }
+
+    if line.trim_start().starts_with(['[', '{']) {
+        return parse_json_reader(line.as_bytes().chain(reader));
+    }
+    if is_csv_header(&line) {
+        return parse_csv_with(line.as_bytes().chain(reader), separator);
+    }
+
+    parse_tables(reader, line, line_number, separator)
+}
+
+/// A benchmark run in the log JMH prints before the results table.
+#[derive(Debug, Default)]
+struct LoggedRun {
+    name: String,
+    params: Vec<(String, String)>,
+    mode: Option<Mode>,
+    threads: Option<i64>,
+    warmup_scores: Vec<f64>,
+}
+
+/// Collects the thread count and the score of the last warmup iteration of every fork from the
+/// log of a run, which looks like:
+///
+/// ```text
+/// # Threads: 4 threads, will synchronize iterations
+/// # Benchmark mode: Throughput, ops/time
+/// # Benchmark: com.example.MyBench.run
+/// # Parameters: (size = 10)
+/// # Warmup Iteration   1: 1234.567 ops/s
+/// Iteration   1: 1250.000 ops/s
+/// ```
+#[derive(Debug, Default)]
+struct WarmupLog {
+    runs: Vec<LoggedRun>,
+    /// The thread count and mode are printed before the name of the benchmark they're for.
+    mode: Option<Mode>,
+    threads: Option<i64>,
+    last_warmup: Option<f64>,
+}
+
+impl WarmupLog {
+    fn read_line(&mut self, line: &str, separator: DecimalSeparator) {
+        let line = line.trim();
+        if let Some(threads) = line.strip_prefix("# Threads: ") {
+            self.threads = threads
+                .split_whitespace()
+                .next()
+                .and_then(|t| t.parse().ok());
+        } else if let Some(mode) = line.strip_prefix("# Benchmark mode: ") {
+            self.mode = logged_mode(mode);
+        } else if let Some(name) = line.strip_prefix("# Benchmark: ") {
+            self.runs.push(LoggedRun {
+                name: name.to_string(),
+                mode: self.mode,
+                threads: self.threads,
+                ..LoggedRun::default()
+            });
+            self.last_warmup = None;
+        } else if let Some(params) = line.strip_prefix("# Parameters: ") {
+            if let Some(run) = self.runs.last_mut() {
+                run.params = logged_params(params);
+            }
+        } else if let Some(iteration) = line.strip_prefix("# Warmup Iteration") {
+            self.last_warmup = iteration_score(iteration, separator);
+        } else if line.starts_with("Iteration") {
+            // The first measurement iteration of a fork ends its warmup
+            if let (Some(score), Some(run)) = (self.last_warmup.take(), self.runs.last_mut()) {
+                run.warmup_scores.push(score);
+            }
+        }
+    }
+
+    /// Gives the results the warmup scores and thread counts of the runs of the same benchmarks.
+    fn attach(self, results: &mut [BenchResult]) {
+        let sorted = |params: &[(String, String)]| {
+            let mut params = params.to_vec();
+            params.sort();
+            params
+        };
+        for run in self.runs {
+            let params = sorted(&run.params);
+            let result = results.iter_mut().find(|r| {
+                r.name == run.name
+                    && run.mode.is_none_or(|m| m == r.mode)
+                    && sorted(&r.params) == params
+            });
+            if let Some(result) = result {
+                result.warmup_scores.extend(run.warmup_scores);
+                if let (0, Some(threads)) = (result.threads, run.threads) {
+                    result.threads = threads;
+                }
+            }
+        }
+    }
+}
+
+/// Parses the long mode names of the log, like `Average time, time/op`.
+fn logged_mode(mode: &str) -> Option<Mode> {
+    let name = mode.split(',').next()?.trim();
+    match name {
+        "Throughput" => Some(Mode::Throughput),
+        "Average time" => Some(Mode::AverageTime),
+        "Sampling time" => Some(Mode::SampleTime),
+        "Single shot invocation time" => Some(Mode::SingleShotTime),
+        _ => name.parse().ok(),
+    }
+}
+
+/// Parses the parameters of the log, like `(size = 10, kind = fast)`.
+fn logged_params(params: &str) -> Vec<(String, String)> {
+    let params = params.trim_start_matches('(').trim_end_matches(')');
+    params
+        .split(", ")
+        .filter_map(|param| {
+            let (name, value) = param.split_once(" = ")?;
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Parses the score of an iteration in the log, like `   1: 1234.567 ops/s`.
+fn iteration_score(iteration: &str, separator: DecimalSeparator) -> Option<f64> {
+    let (_, rest) = iteration.split_once(':')?;
+    parse_score(rest.split_whitespace().next()?, separator).filter(|s| s.is_finite())
+}
+
+/// Parses the text output that follows `line`, which was read from `line_number`.
+///
+/// JMH prints a lot of other output around the results, so only the blocks that start
+/// with a results table header are parsed. If there aren't any, the last block is.
+fn parse_tables<R: BufRead>(
+    mut reader: R,
+    mut line: String,
+    mut line_number: usize,
+    separator: DecimalSeparator,
+) -> Result<(Vec<BenchResult>, Vec<ParseError>), std::io::Error> {
+    let mut rows = vec![];
+    let mut found_table = false;
+    let mut block = String::new();
+    let mut block_start = line_number;
+    let mut last_block = (String::new(), block_start);
+    let mut log = WarmupLog::default();
+
+    loop {
+        log.read_line(&line, separator);
+        let at_end = line.is_empty();
+        if line.trim().is_empty() {
+            if is_table_header(block.lines().next().unwrap_or_default()) {
+                rows.extend(parse_lines(&block, block_start, separator));
+                found_table = true;
+            } else if !found_table && !block.is_empty() {
+                // Runs of blank lines, like the ones output often ends with, make empty blocks
+                // that mustn't replace the last one with results
+                last_block = (std::mem::take(&mut block), block_start);
+            }
+            block.clear();
+            block_start = line_number + 1;
+        } else {
+            block.push_str(&line);
+        }
+
+        if at_end {
+            break;
+        }
+        line.clear();
+        reader.read_line(&mut line)?;
+        line_number += 1;
+    }
+
+    if !found_table {
+        let (block, block_start) = last_block;
+        rows.extend(parse_lines(&block, block_start, separator));
+    }
+
+    let (mut results, errors) = collect_rows(rows.into_iter());
+    log.attach(&mut results);
+    Ok((results, errors))
+}
+
+pub fn parse_file<P: AsRef<Path>>(
+    path: P,
+) -> Result<(Vec<BenchResult>, Vec<ParseError>), std::io::Error> {
+    parse_file_with(path, DecimalSeparator::Point)
+}
+
+/// Like [`parse_file`], reading the scores of text and CSV output with the given decimal
+/// separator.
+pub fn parse_file_with<P: AsRef<Path>>(
+    path: P,
+    separator: DecimalSeparator,
+) -> Result<(Vec<BenchResult>, Vec<ParseError>), std::io::Error> {
+    let file = File::open(&path)?;
+    if is_json_path(path.as_ref()) {
+        return parse_json_reader(file);
+    }
+    if is_csv_path(path.as_ref()) {
+        return parse_csv_with(file, separator);
+    }
+
+    parse_reader_with(file, separator)
+}
+
+/// Replaces the scores with the statistic of their raw data. Returns how many of the results
+/// have no raw data, so they keep the score JMH reported.
+pub fn use_statistic(results: &mut [BenchResult], statistic: Statistic) -> usize {
+    let mut missing = 0;
+    for result in results {
+        match statistic.of(&result.raw_data) {
+            Some(score) => result.score = score,
+            None => missing += 1,
+        }
+    }
+    missing
+}
+
+/// Returns `(new - old) / old`, which is infinite when only the old score is zero and zero
+/// when both are.
+fn relative_diff(old: f64, new: f64) -> f64 {
+    if old == 0.0 {
+        if new == 0.0 {
+            0.0
+        } else {
+            f64::INFINITY.copysign(new)
+        }
+    } else {
+        (new - old) / old
+    }
+}
+
+/// Returns the error of `new / old`, using the standard propagation of relative errors.
+fn ratio_error(old: f64, old_error: f64, new: f64, new_error: f64) -> f64 {
+    if old == 0.0 {
+        return 0.0;
+    }
+    (new_error / old).hypot(new * old_error / (old * old))
+}
+
+/// Returns how many seconds are in one of the given JMH time unit.
+fn seconds_per(unit: &str) -> Option<f64> {
+    match unit {
+        "ns" => Some(1e-9),
+        "us" | "µs" => Some(1e-6),
+        "ms" => Some(1e-3),
+        "s" => Some(1.0),
+        "min" => Some(60.0),
+        "hr" => Some(3600.0),
+        "day" => Some(86400.0),
+        _ => None,
+    }
+}
+
+/// Converts a score into the base unit of its family, `s/op` for time units and `ops/s` for
+/// throughput units. Returns `None` if the units aren't recognised.
+pub fn normalize(score: f64, units: &str) -> Option<(f64, String)> {
+    if let Some(time) = units.strip_suffix("/op") {
+        return seconds_per(time).map(|s| (score * s, "s/op".to_string()));
+    }
+    if let Some(time) = units.strip_prefix("ops/") {
+        return seconds_per(time).map(|s| (score / s, "ops/s".to_string()));
+    }
+    None
+}
+
+/// Converts a score from one unit to another, if they are the same or belong to the same family.
+fn convert_units(score: f64, from: &str, to: &str) -> Option<f64> {
+    if from == to {
+        return Some(score);
+    }
+    let (base_score, base_units) = normalize(score, from)?;
+    let (factor, to_base_units) = normalize(1.0, to)?;
+    (base_units == to_base_units).then(|| base_score / factor)
+}
+
+/// Compares two results, converting the old score into the units of the new one if they differ.
+pub fn calculate_delta(
+    new_bench_result: &BenchResult,
+    old_bench_result: &BenchResult,
+) -> BenchDiff {
+    let to_new_units = |value| {
+        convert_units(value, &old_bench_result.units, &new_bench_result.units).unwrap_or(value)
+    };
+    let old_score = to_new_units(old_bench_result.score);
+    let old_error = to_new_units(old_bench_result.error);
+    let new_score = new_bench_result.score;
+    let new_error = new_bench_result.error;
+
+    BenchDiff {
+        name: new_bench_result.name.clone(),
+        params: new_bench_result.params.clone(),
+        mode: new_bench_result.mode,
+        new_score,
+        old_score,
+        diff: relative_diff(old_score, new_score),
+        units: new_bench_result.units.clone(),
+        old_error,
+        new_error,
+        old_count: old_bench_result.count,
+        new_count: new_bench_result.count,
+        old_threads: old_bench_result.threads,
+        new_threads: new_bench_result.threads,
+        diff_error: ratio_error(old_score, old_error, new_score, new_error),
+        p99_diff: old_bench_result
+            .percentiles
+            .zip(new_bench_result.percentiles)
+            .map(|(old, new)| relative_diff(to_new_units(old.p99), new.p99)),
+        significant: (new_score - old_score).abs() > old_error + new_error,
+        noise: false,
+        high_variance: false,
+        inverted: false,
+    }
+}
+
+/// The parameter `--key-field id` matches benchmarks on.
+pub const ID_PARAM: &str = "id";
+
+/// What the results of the two files are matched on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum MatchKey {
+    /// The name and parameters.
+    #[default]
+    Name,
+    /// The value of a parameter, for harnesses that give each benchmark a stable id while its
+    /// name changes. Results that both don't have the parameter are matched by name.
+    Param(String),
+}
+
+impl MatchKey {
+    /// Returns true if the results are for the same benchmark, whatever their units are.
+    fn matches(&self, a: &BenchResult, b: &BenchResult) -> bool {
+        if a.mode != b.mode {
+            return false;
+        }
+        if let Self::Param(param) = self {
+            let value = |r: &BenchResult| {
+                r.params
+                    .iter()
+                    .find(|(name, _)| name == param)
+                    .map(|(_, value)| value.clone())
+            };
+            match (value(a), value(b)) {
+                (None, None) => {}
+                (a, b) => return a == b,
+            }
+        }
+        a.name == b.name && a.params == b.params
+    }
+}
+
+fn same_benchmark(a: &BenchResult, b: &BenchResult) -> bool {
+    same_benchmark_by(a, b, &MatchKey::Name)
+}
+
+fn same_benchmark_by(a: &BenchResult, b: &BenchResult, key: &MatchKey) -> bool {
+    key.matches(a, b) && convert_units(1.0, &a.units, &b.units).is_some()
+}
+
+/// Returns true if both results are for the same benchmark, but in units that can't be
+/// converted to each other.
+fn same_except_units(a: &BenchResult, b: &BenchResult, key: &MatchKey) -> bool {
+    key.matches(a, b) && convert_units(1.0, &a.units, &b.units).is_none()
+}
+
+pub fn compare_benchmark_results(
+    old_results: Vec<BenchResult>,
+    new_results: Vec<BenchResult>,
+) -> Vec<BenchComparison> {
+    compare_benchmark_results_by(old_results, new_results, &MatchKey::Name)
+}
+
+/// Compares the results, matching the old and new results of each benchmark on the `key`.
+pub fn compare_benchmark_results_by(
+    old_results: Vec<BenchResult>,
+    new_results: Vec<BenchResult>,
+    key: &MatchKey,
+) -> Vec<BenchComparison> {
+    let added: Vec<BenchComparison> = new_results
+        .iter()
+        .filter(|n| {
+            !old_results
+                .iter()
+                .any(|o| same_benchmark_by(n, o, key) || same_except_units(n, o, key))
+        })
+        .cloned()
+        .map(BenchComparison::Added)
+        .collect();
+
+    let mut comparisons: Vec<BenchComparison> = old_results
+        .into_iter()
+        .map(|o| {
+            if let Some(n) = new_results.iter().find(|n| same_benchmark_by(n, &o, key)) {
+                BenchComparison::Changed(calculate_delta(n, &o))
+            } else if let Some(n) = new_results.iter().find(|n| same_except_units(n, &o, key)) {
+                BenchComparison::UnitMismatch {
+                    old: o,
+                    new: n.clone(),
+                }
+            } else {
+                BenchComparison::Removed(o)
+            }
+        })
+        .collect();
+
+    comparisons.extend(added);
+
+    comparisons
+}
+
+/// Returns the results of a secondary metric as results of their own, so they can be compared.
+///
+/// The metric is taken from the secondary metrics of JSON output, or from the rows of text
+/// output that JMH names like `MyBench.run:·gc.alloc.rate.norm`. Since the metric doesn't have
+/// a mode of its own, it's compared as a throughput if it's measured in operations per unit
+/// of time, and as lower is better otherwise.
+pub fn secondary_results(results: &[BenchResult], metric: &str) -> Vec<BenchResult> {
+    let mode = |units: &str| {
+        if units.starts_with("ops/") {
+            Mode::Throughput
+        } else {
+            Mode::AverageTime
+        }
+    };
+
+    results
+        .iter()
+        .filter_map(|result| {
+            if let Some((_, m)) = result.secondary_metrics.iter().find(|(n, _)| n == metric) {
+                return Some(BenchResult {
+                    mode: mode(&m.units),
+                    score: m.score,
+                    error: m.error,
+                    units: m.units.clone(),
+                    percentiles: None,
+                    secondary_metrics: vec![],
+                    raw_data: vec![],
+                    warmup_scores: vec![],
+                    ..result.clone()
+                });
+            }
+
+            let name = secondary_row_name(result, metric)?;
+            Some(BenchResult {
+                name: name.to_string(),
+                mode: mode(&result.units),
+                ..result.clone()
+            })
+        })
+        .collect()
+}
+
+/// Returns true if the result is a row of text output for the secondary metric, which
+/// `secondary_results` turns into a result of the benchmark it's for.
+pub fn is_secondary_row(result: &BenchResult, metric: &str) -> bool {
+    secondary_row_name(result, metric).is_some()
+}
+
+/// Returns the name of the benchmark a row like `MyBench.run:·gc.alloc.rate.norm` is for.
+fn secondary_row_name<'a>(result: &'a BenchResult, metric: &str) -> Option<&'a str> {
+    let name = result.name.strip_suffix(metric)?;
+    name.strip_suffix(":·").or_else(|| name.strip_suffix(':'))
+}
+
+/// Replaces the prefix of the names starting with one of the old prefixes with its new
+/// prefix, so benchmarks can still be compared after their class or package was renamed.
+/// Only the first matching rename is applied.
+pub fn rename_prefixes(results: &mut [BenchResult], renames: &[(String, String)]) {
+    for result in results {
+        let renamed = renames.iter().find_map(|(old, new)| {
+            let rest = result.name.strip_prefix(old.as_str())?;
+            Some(format!("{new}{rest}"))
+        });
+        if let Some(name) = renamed {
+            result.name = name;
+        }
+    }
+}
+
+/// Renames old results to the casing of the new ones, for benchmarks whose names only differ
+/// in case.
+pub fn match_names_ignoring_case(old_results: &mut [BenchResult], new_results: &[BenchResult]) {
+    for old in old_results.iter_mut() {
+        if new_results.iter().any(|n| same_benchmark(n, old)) {
+            continue;
+        }
+        let renamed = new_results.iter().find(|n| {
+            n.name.eq_ignore_ascii_case(&old.name)
+                && n.params == old.params
+                && n.mode == old.mode
+                && convert_units(1.0, &n.units, &old.units).is_some()
+        });
+        if let Some(new) = renamed {
+            old.name = new.name.clone();
+        }
+    }
+}
+
+/// Converts old results to the reciprocal mode of the new ones, for benchmarks that were
+/// measured as throughput in one file and as average time in the other.
+pub fn match_reciprocals(old_results: &mut [BenchResult], new_results: &[BenchResult]) {
+    for old in old_results.iter_mut() {
+        if new_results.iter().any(|n| same_benchmark(n, old)) {
+            continue;
+        }
+        if let Some(reciprocal) = old
+            .reciprocal()
+            .filter(|r| new_results.iter().any(|n| same_benchmark(n, r)))
+        {
+            *old = reciprocal;
+        }
+    }
+}
+
+/// Returns the distinct modes of the results, in the order JMH lists them.
+pub fn modes_of(results: &[BenchResult]) -> Vec<Mode> {
+    [
+        Mode::Throughput,
+        Mode::AverageTime,
+        Mode::SampleTime,
+        Mode::SingleShotTime,
+    ]
+    .into_iter()
+    .filter(|&mode| results.iter().any(|r| r.mode == mode))
+    .collect()
+}
+
+/// Finds benchmarks that can't be compared because they were run under a different mode in
+/// each file, and would otherwise show up as removed and added.
+pub fn find_mode_changes(
+    old_results: &[BenchResult],
+    new_results: &[BenchResult],
+) -> Vec<ModeChange> {
+    let unmatched_old = old_results
+        .iter()
+        .filter(|o| !new_results.iter().any(|n| same_benchmark(n, o)));
+
+    unmatched_old
+        .filter_map(|o| {
+            new_results
+                .iter()
+                .filter(|n| !old_results.iter().any(|o| same_benchmark(n, o)))
+                .find(|n| n.name == o.name && n.params == o.params && n.mode != o.mode)
+                .map(|n| ModeChange {
+                    name: o.display_name(),
+                    old_mode: o.mode,
+                    new_mode: n.mode,
+                })
+        })
+        .collect()
+}
+
+/// Strips the package prefix that all the names have in common, always keeping the class and
+/// method, so `com.example.Bench.run` becomes `Bench.run`. The dots of a secondary metric, like
+/// in `com.example.Bench.run:·gc.alloc.rate`, aren't part of the package.
+pub fn shorten_names<'a>(names: impl IntoIterator<Item = &'a mut String>) {
+    let mut names: Vec<&mut String> = names.into_iter().collect();
+
+    let mut prefix: Option<&str> = None;
+    for name in &names {
+        // Everything before the class can be stripped
+        let benchmark = name.split(":·").next().unwrap_or_default();
+        let package = match benchmark.rsplitn(3, '.').nth(2) {
+            Some(package) => &name[..package.len() + 1],
+            None => "",
+        };
+        prefix = Some(match prefix {
+            None => package,
+            Some(prefix) => {
+                let common = prefix
+                    .split_inclusive('.')
+                    .zip(package.split_inclusive('.'))
+                    .take_while(|(a, b)| a == b)
+                    .map(|(a, _)| a.len())
+                    .sum();
+                &prefix[..common]
+            }
+        });
+    }
+
+    let prefix_len = prefix.map_or(0, str::len);
+    for name in names.iter_mut() {
+        name.drain(..prefix_len);
+    }
+}
+
+/// Marks the diffs smaller than `band` percent as noise.
+pub fn mark_noise(comparisons: &mut [BenchComparison], band: f64) {
+    for comparison in comparisons {
+        if let BenchComparison::Changed(diff) = comparison {
+            diff.noise = diff.diff.abs() * 100.0 < band;
+        }
+    }
+}
+
+/// Swaps which direction of change counts as an improvement for all the diffs.
+pub fn invert_improvements(comparisons: &mut [BenchComparison]) {
+    for comparison in comparisons {
+        if let BenchComparison::Changed(diff) = comparison {
+            diff.inverted = true;
+        }
+    }
+}
+
+/// Marks the diffs where the error of the old or new score is more than `max_rsd` percent of
+/// the score as high variance.
+pub fn mark_high_variance(comparisons: &mut [BenchComparison], max_rsd: f64) {
+    let too_noisy = |score: f64, error: f64| error.abs() * 100.0 > score.abs() * max_rsd;
+    for comparison in comparisons {
+        if let BenchComparison::Changed(diff) = comparison {
+            diff.high_variance = too_noisy(diff.old_score, diff.old_error)
+                || too_noisy(diff.new_score, diff.new_error);
+        }
+    }
+}
+
+/// How bad a change is, for sorting. Benchmarks without a diff sort after all others.
+fn severity(diff: Option<&BenchDiff>) -> f64 {
+    diff.map_or(f64::NEG_INFINITY, |d| -d.improvement())
+}
+
+pub fn sort_comparisons(comparisons: &mut [BenchComparison], order: SortOrder) {
+    match order {
+        SortOrder::None => {}
+        SortOrder::Name => comparisons.sort_by_cached_key(|c| (c.display_name(), c.mode())),
+        SortOrder::Diff => comparisons.sort_by(|a, b| {
+            severity(b.as_changed())
+                .total_cmp(&severity(a.as_changed()))
+                .then_with(|| (a.display_name(), a.mode()).cmp(&(b.display_name(), b.mode())))
+        }),
+    }
+}
+
+/// Moves the comparisons of each class next to each other, keeping the order of the classes'
+/// first benchmarks and of the benchmarks within each class.
+pub fn group_by_class(comparisons: &mut Vec<BenchComparison>) {
+    let mut classes: Vec<&str> = vec![];
+    for comparison in comparisons.iter() {
+        if !classes.contains(&comparison.class()) {
+            classes.push(comparison.class());
+        }
+    }
+    let order: Vec<usize> = comparisons
+        .iter()
+        .map(|c| {
+            classes
+                .iter()
+                .position(|class| *class == c.class())
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut keyed: Vec<(usize, BenchComparison)> =
+        order.into_iter().zip(comparisons.drain(..)).collect();
+    keyed.sort_by_key(|(i, _)| *i);
+    comparisons.extend(keyed.into_iter().map(|(_, c)| c));
+}
+
+/// Returns the heading of a class's rows when they're grouped, with the geomean improvement of
+/// the benchmarks that could be compared.
+pub fn class_heading(class: &str, comparisons: &[BenchComparison]) -> String {
+    let class = if class.is_empty() {
+        "(no class)"
+    } else {
+        class
+    };
+    match Summary::from_diffs(comparisons.iter().filter_map(BenchComparison::as_changed)) {
+        Some(summary) => format!("{class}: geomean improvement {}", percent(summary.geomean)),
+        None => format!("{class}:"),
+    }
+}
+
+pub fn sort_trends(trends: &mut [BenchTrend], order: SortOrder) {
+    match order {
+        SortOrder::None => {}
+        SortOrder::Name => trends.sort_by_cached_key(|t| (t.display_name(), t.mode)),
+        SortOrder::Diff => trends.sort_by(|a, b| {
+            severity(b.diff().as_ref())
+                .total_cmp(&severity(a.diff().as_ref()))
+                .then_with(|| (a.display_name(), a.mode).cmp(&(b.display_name(), b.mode)))
+        }),
+    }
+}
+
+/// Lines up the results of several runs by name, mode and units.
+pub fn compare_trend(runs: Vec<Vec<BenchResult>>) -> Vec<BenchTrend> {
+    let count = runs.len();
+    let mut trends: Vec<BenchTrend> = vec![];
+
+    for (i, results) in runs.into_iter().enumerate() {
+        for result in results {
+            let existing = trends.iter_mut().find(|t| {
+                t.name == result.name
+                    && t.params == result.params
+                    && t.mode == result.mode
+                    && convert_units(1.0, &t.units, &result.units).is_some()
+            });
+            match existing {
+                Some(trend) => trend.runs[i] = Some(result),
+                None => {
+                    let mut trend = BenchTrend {
+                        name: result.name.clone(),
+                        params: result.params.clone(),
+                        mode: result.mode,
+                        units: result.units.clone(),
+                        runs: (0..count).map(|_| None).collect(),
+                    };
+                    trend.runs[i] = Some(result);
+                    trends.push(trend);
+                }
+            }
+        }
+    }
+
+    trends
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::io::Cursor;
+
+    fn result(name: &str, mode: Mode, score: f64, units: &str) -> BenchResult {
+        BenchResult {
+            name: name.to_string(),
+            params: vec![],
+            mode,
+            threads: 0,
+            count: 5,
+            score,
+            error: 0.0,
+            units: units.to_string(),
+            percentiles: None,
+            secondary_metrics: vec![],
+            raw_data: vec![],
+            warmup_scores: vec![],
+        }
+    }
+
+    #[test]
+    fn json_entries_without_a_score_are_errors() {
+        let input = r#"[
+            {"benchmark": "MyBench.a", "mode": "avgt", "measurementIterations": 5},
+            {
+                "benchmark": "MyBench.b",
+                "mode": "avgt",
+                "measurementIterations": 5,
+                "primaryMetric": {"scoreError": 0.01, "scoreUnit": "ns/op"}
+            },
+            {
+                "benchmark": "MyBench.c",
+                "mode": "avgt",
+                "measurementIterations": 5,
+                "primaryMetric": {"score": 1.0, "scoreError": 0.01, "scoreUnit": "ns/op"}
+            }
+        ]"#;
+        let (results, errors) = parse_json(input);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "MyBench.c");
+        assert!(matches!(
+            errors[..],
+            [
+                ParseError::MissingMetric { .. },
+                ParseError::MissingField { field: "score", .. }
+            ]
+        ));
+        let messages: Vec<String> = errors.iter().map(ParseError::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "benchmark 1: missing primaryMetric",
+                "benchmark 2: missing score"
+            ]
+        );
+    }
+
+    #[test]
+    fn zero_old_score_is_an_infinite_diff() {
+        let old = result("MyBench.noop", Mode::SingleShotTime, 0.0, "ns/op");
+        let new = result("MyBench.noop", Mode::SingleShotTime, 2.0, "ns/op");
+        let diff = calculate_delta(&new, &old);
+        assert_eq!(diff.diff_str(), "+Inf%");
+        assert_eq!(diff.diff_with_error_str(), "+Inf%");
+
+        let diff = calculate_delta(&old, &old);
+        assert_eq!(diff.diff_str(), "+0.00000%");
+    }
+
+    #[test]
+    fn results_are_paired_by_mode() {
+        let old = vec![
+            result("MyBench.run", Mode::AverageTime, 10.0, "ns/op"),
+            result("MyBench.run", Mode::SampleTime, 20.0, "ns/op"),
+        ];
+        let new = vec![
+            result("MyBench.run", Mode::SampleTime, 22.0, "ns/op"),
+            result("MyBench.run", Mode::AverageTime, 11.0, "ns/op"),
+        ];
+        let comparisons = compare_benchmark_results(old, new);
+        assert_eq!(comparisons.len(), 2);
+        for comparison in &comparisons {
+            let diff = comparison.as_changed().expect("both modes are compared");
+            let old_score = match diff.mode {
+                Mode::AverageTime => 10.0,
+                _ => 20.0,
+            };
+            assert_eq!(diff.old_score, old_score);
+            assert!((diff.diff - 0.1).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn mode_changes_are_found() {
+        let old = vec![result("MyBench.run", Mode::Throughput, 10.0, "ops/s")];
+        let new = vec![result("MyBench.run", Mode::AverageTime, 0.1, "s/op")];
+        let changes = find_mode_changes(&old, &new);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].name, "MyBench.run");
+        assert!(matches!(changes[0].old_mode, Mode::Throughput));
+        assert!(matches!(changes[0].new_mode, Mode::AverageTime));
+    }
+
+    #[test]
+    fn row_with_error_column() {
+        let row = parse_row("MyBench.run  avgt  5  1.230 ± 0.010  ns/op", 1).unwrap();
+        assert_eq!(row.score, 1.23);
+        assert_eq!(row.error, 0.01);
+        assert_eq!(row.units, "ns/op");
+    }
+
+    #[test]
+    fn row_without_error_column() {
+        let row = parse_row("MyBench.run  ss  1  1.230  ns/op", 1).unwrap();
+        assert_eq!(row.count, 1);
+        assert_eq!(row.score, 1.23);
+        assert_eq!(row.error, 0.0);
+        assert_eq!(row.units, "ns/op");
+    }
+
+    #[test]
+    fn row_without_count_column() {
+        let row = parse_row("MyBench.run  thrpt  1234.5 ± 10.0  ops/s", 1).unwrap();
+        assert_eq!(row.count, 0);
+        assert_eq!(row.score, 1234.5);
+        assert_eq!(row.error, 10.0);
+        assert_eq!(row.units, "ops/s");
+
+        // An integral score is still the score when nothing that looks like one follows it
+        let row = parse_row("MyBench.run  thrpt  1234 ± 10  ops/s", 1).unwrap();
+        assert_eq!(row.count, 0);
+        assert_eq!(row.score, 1234.0);
+    }
+
+    #[test]
+    fn every_results_table_is_parsed() {
+        let input = "\
+# Run progress: 0.00% complete
+
+Benchmark    Mode  Cnt  Score   Error  Units
+MyBench.a    avgt    5  1.000 ± 0.010  ns/op
+
+Benchmark    Mode  Cnt  Score   Error  Units
+MyBench.b    avgt    5  2.000 ± 0.010  ns/op
+
+Do not assume the numbers tell you what you want them to tell.
+";
+        let (results, errors) = parse_str(input);
+        assert!(errors.is_empty(), "{errors:?}");
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["MyBench.a", "MyBench.b"]);
+    }
+
+    #[test]
+    fn scientific_notation_scores() {
+        let row = parse_row("MyBench.run  avgt  5  1.23E-4 ± 1.0E-6  s/op", 1).unwrap();
+        assert_eq!(row.score, 1.23e-4);
+        assert_eq!(row.error, 1.0e-6);
+
+        let row = parse_row("MyBench.run  thrpt  5  1.234567E7 ± 1000  ops/s", 1).unwrap();
+        assert_eq!(row.score, 1.234567e7);
+    }
+
+    #[test]
+    fn score_formatting() {
+        let default = NumberFormat::default();
+        assert_eq!(default.format(1.234567e7), "12345670");
+        assert_eq!(default.format(1.23e-4), "0.000123");
+        assert_eq!(default.format(12.345), "12.3");
+
+        let fixed = NumberFormat {
+            precision: Some(2),
+            ..NumberFormat::default()
+        };
+        assert_eq!(fixed.format(12.345), "12.35");
+
+        let si = NumberFormat {
+            si_suffixes: true,
+            ..NumberFormat::default()
+        };
+        assert_eq!(si.format(1.234567e7), "12.3M");
+        assert_eq!(si.format(1500.0), "1.50k");
+    }
+
+    #[test]
+    fn duplicate_rows_are_reported() {
+        let input = "\
+Benchmark    Mode  Cnt  Score   Error  Units
+MyBench.a    avgt    5  1.000 ± 0.010  ns/op
+MyBench.a    avgt    5  2.000 ± 0.010  ns/op
+";
+        let (results, errors) = parse_str(input);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].score, 1.0);
+        assert!(matches!(
+            &errors[..],
+            [ParseError::DuplicateBenchmark {
+                at: Location::Line(3),
+                name,
+                mode: Mode::AverageTime,
+            }] if name == "MyBench.a"
+        ));
+    }
+
+    #[test]
+    fn rows_with_different_params_are_not_duplicates() {
+        let input = "\
+Benchmark    (size)  Mode  Cnt  Score   Error  Units
+MyBench.a        10  avgt    5  1.000 ± 0.010  ns/op
+MyBench.a       100  avgt    5  2.000 ± 0.010  ns/op
+";
+        let (results, errors) = parse_str(input);
+        assert!(errors.is_empty(), "{errors:?}");
+        let names: Vec<String> = results.iter().map(BenchResult::display_name).collect();
+        assert_eq!(names, ["MyBench.a[size=10]", "MyBench.a[size=100]"]);
+    }
+
+    /// Generates a results table at a time, remembering the largest read it was asked for.
+    struct GeneratedTables {
+        tables: usize,
+        rows: usize,
+        pending: Vec<u8>,
+        largest_read: usize,
+    }
+
+    impl Read for GeneratedTables {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.largest_read = self.largest_read.max(buf.len());
+            if self.pending.is_empty() && self.tables > 0 {
+                self.tables -= 1;
+                let mut table = "Benchmark  Mode  Cnt  Score   Error  Units\n".to_string();
+                for row in 0..self.rows {
+                    table.push_str(&format!(
+                        "MyBench.t{}r{row}  avgt  5  1.000 ± 0.010  ns/op\n",
+                        self.tables
+                    ));
+                }
+                table.push('\n');
+                self.pending = table.into_bytes();
+            }
+            let n = buf.len().min(self.pending.len());
+            buf[..n].copy_from_slice(&self.pending[..n]);
+            self.pending.drain(..n);
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn large_input_is_read_incrementally() {
+        let mut reader = GeneratedTables {
+            tables: 50,
+            rows: 200,
+            pending: vec![],
+            largest_read: 0,
+        };
+        let (results, errors) = parse_reader(&mut reader).unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(results.len(), 50 * 200);
+        // Reading the whole input at once would ask for ever larger reads
+        assert!(reader.largest_read <= 64 * 1024, "{}", reader.largest_read);
+    }
+
+    #[test]
+    fn errors_are_propagated_to_the_diff() {
+        let mut old = result("MyBench.run", Mode::AverageTime, 100.0, "ns/op");
+        old.error = 2.0;
+        let mut new = result("MyBench.run", Mode::AverageTime, 110.0, "ns/op");
+        new.error = 3.0;
+
+        // The relative errors of 3 / 100 and 110 * 2 / 100², added in quadrature
+        let expected = (0.03f64 * 0.03 + 0.022 * 0.022).sqrt();
+        assert!((ratio_error(100.0, 2.0, 110.0, 3.0) - expected).abs() < 1e-12);
+        assert_eq!(ratio_error(0.0, 2.0, 110.0, 3.0), 0.0);
+
+        let diff = calculate_delta(&new, &old);
+        assert!((diff.diff_error - expected).abs() < 1e-12);
+        assert_eq!(diff.diff_with_error_str(), "+10.00000% ± 3.72022%");
+    }
+
+    #[test]
+    fn unconvertible_units_are_a_mismatch() {
+        let old = vec![result("MyBench.run", Mode::AverageTime, 10.0, "ns/op")];
+        let new = vec![result("MyBench.run", Mode::AverageTime, 10.0, "B/op")];
+        let comparisons = compare_benchmark_results(old, new);
+        assert_eq!(comparisons.len(), 1);
+        assert!(matches!(
+            &comparisons[0],
+            BenchComparison::UnitMismatch { old, new } if old.units == "ns/op" && new.units == "B/op"
+        ));
+        let cells = comparisons[0].cells(&NumberFormat::default());
+        assert_eq!(cells.last().unwrap(), "UNIT MISMATCH (ns/op -> B/op)");
+    }
+
+    #[test]
+    fn convertible_units_are_compared() {
+        let old = vec![result("MyBench.run", Mode::AverageTime, 1000.0, "ns/op")];
+        let new = vec![result("MyBench.run", Mode::AverageTime, 2.0, "us/op")];
+        let comparisons = compare_benchmark_results(old, new);
+        let diff = comparisons[0].as_changed().expect("the units convert");
+        assert!((diff.diff - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn throughput_is_compared_with_the_reciprocal_of_average_time() {
+        // 4 ops/s is 0.25 s/op, so the new run takes twice as long
+        let mut old = vec![result("MyBench.run", Mode::Throughput, 4.0, "ops/s")];
+        let new = vec![result("MyBench.run", Mode::AverageTime, 0.5, "s/op")];
+        match_reciprocals(&mut old, &new);
+        assert_eq!(old[0].mode, Mode::AverageTime);
+        assert_eq!(old[0].units, "s/op");
+        assert_eq!(old[0].score, 0.25);
+
+        let comparisons = compare_benchmark_results(old, new);
+        let diff = comparisons[0].as_changed().expect("the modes now match");
+        assert!((diff.diff - 1.0).abs() < 1e-9);
+        assert_eq!(diff.status(), "worse");
+    }
+
+    #[test]
+    fn reciprocals_are_only_used_when_they_match() {
+        let mut old = vec![result("MyBench.run", Mode::Throughput, 4.0, "ops/s")];
+        let new = vec![result("MyBench.other", Mode::AverageTime, 0.5, "s/op")];
+        match_reciprocals(&mut old, &new);
+        assert_eq!(old[0].mode, Mode::Throughput);
+    }
+
+    #[test]
+    fn param_values_can_contain_spaces() {
+        let input = "\
+Benchmark     (kind)  (size)  Mode  Cnt  Score   Error  Units
+MyBench.run      a b      10  avgt    5  1.000 ± 0.010  ns/op
+MyBench.run   single     100  avgt    5  2.000 ± 0.010  ns/op
+";
+        let (results, errors) = parse_str(input);
+        assert!(errors.is_empty(), "{errors:?}");
+        let names: Vec<String> = results.iter().map(BenchResult::display_name).collect();
+        assert_eq!(
+            names,
+            [
+                "MyBench.run[kind=a b,size=10]",
+                "MyBench.run[kind=single,size=100]"
+            ]
+        );
+        assert_eq!(results[0].mode, Mode::AverageTime);
+        assert_eq!(results[1].score, 2.0);
+    }
+
+    #[test]
+    fn non_finite_scores_are_skipped() {
+        let input = "\
+Benchmark    Mode  Cnt  Score   Error  Units
+MyBench.a    avgt    5    NaN ±   NaN  ns/op
+MyBench.b    avgt    5      ∞          ns/op
+MyBench.c    avgt    5  1.000 ± 0.010  ns/op
+";
+        let (results, errors) = parse_str(input);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "MyBench.c");
+        let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "line 2: non-finite score 'NaN'",
+                "line 3: non-finite score '∞'"
+            ]
+        );
+    }
+
+    #[test]
+    fn names_can_match_ignoring_case() {
+        let mut old = vec![result("MyBench.foo", Mode::AverageTime, 10.0, "ns/op")];
+        let new = vec![result("MyBench.Foo", Mode::AverageTime, 11.0, "ns/op")];
+        assert!(compare_benchmark_results(old.clone(), new.clone())
+            .iter()
+            .all(BenchComparison::is_missing));
+
+        match_names_ignoring_case(&mut old, &new);
+        let comparisons = compare_benchmark_results(old, new);
+        assert_eq!(comparisons.len(), 1);
+        let diff = comparisons[0].as_changed().expect("the names match");
+        assert_eq!(diff.name, "MyBench.Foo");
+    }
+
+    #[test]
+    fn headers_match_the_fields() {
+        let old = result("MyBench.run", Mode::AverageTime, 10.0, "ns/op");
+        let new = result("MyBench.run", Mode::AverageTime, 12.0, "ns/op");
+        let diff = calculate_delta(&new, &old);
+
+        let headers = BenchDiff::headers();
+        let fields = diff.fields();
+        assert_eq!(headers.len(), BenchDiff::LENGTH);
+        assert_eq!(fields.len(), BenchDiff::LENGTH);
+        let field = |header: &str| {
+            let column = headers.iter().position(|h| h == header).unwrap();
+            fields[column].to_string()
+        };
+        assert_eq!(field("name"), "MyBench.run");
+        assert_eq!(field("mode"), "avgt");
+        assert_eq!(field("old score"), "10.0");
+        assert_eq!(field("new score"), "12.0");
+        assert_eq!(field("units"), "ns/op");
+        assert_eq!(field("delta"), "+2.00 ns/op");
+        assert_eq!(field("status"), "worse");
+
+        let labeled = BenchDiff::headers_with_labels("main", "pr");
+        assert_eq!(labeled[2], "main score");
+        assert_eq!(labeled[3], "pr score");
+    }
+
+    #[test]
+    fn parse_reader_reads_every_format_from_a_cursor() {
+        let text = "\
+Benchmark    Mode  Cnt  Score   Error  Units
+MyBench.run  avgt    5  1.000 ± 0.010  ns/op
+";
+        let json = r#"[{
+            "benchmark": "MyBench.run",
+            "mode": "avgt",
+            "measurementIterations": 5,
+            "primaryMetric": {"score": 1.0, "scoreError": 0.01, "scoreUnit": "ns/op"}
+        }]"#;
+        let csv = "\
+\"Benchmark\",\"Mode\",\"Threads\",\"Samples\",\"Score\",\"Score Error (99.9%)\",\"Unit\"
+\"MyBench.run\",\"avgt\",1,5,1.000000,0.010000,\"ns/op\"
+";
+        for input in [text, json, csv] {
+            let (results, errors) = parse_reader(Cursor::new(input)).unwrap();
+            assert!(errors.is_empty(), "{errors:?}");
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].name, "MyBench.run");
+            assert_eq!(results[0].mode, Mode::AverageTime);
+            assert_eq!(results[0].score, 1.0);
+            assert_eq!(results[0].error, 0.01);
+            assert_eq!(results[0].units, "ns/op");
+        }
+    }
+
+    #[test]
+    fn parse_reader_of_an_empty_cursor() {
+        let (results, errors) = parse_reader(Cursor::new("")).unwrap();
+        assert!(results.is_empty());
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn block_with_a_header() {
+        let (results, errors) = parse_block(
+            "Benchmark    Mode  Cnt  Score   Error  Units\n\
+             MyBench.a    avgt    5  1.000 ± 0.010  ns/op\n",
+        );
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "MyBench.a");
+    }
+
+    #[test]
+    fn block_without_a_header() {
+        let (results, errors) = parse_block(
+            "\n\nMyBench.a    avgt    5  1.000 ± 0.010  ns/op\n\
+             MyBench.b    avgt    5  2.000 ± 0.010  ns/op\n",
+        );
+        assert!(errors.is_empty(), "{errors:?}");
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["MyBench.a", "MyBench.b"]);
+    }
+
+    #[test]
+    fn statistics_of_samples() {
+        let odd = [5.0, 1.0, 3.0];
+        assert_eq!(Statistic::Mean.of(&odd), Some(3.0));
+        assert_eq!(Statistic::Median.of(&odd), Some(3.0));
+        assert_eq!(Statistic::Min.of(&odd), Some(1.0));
+        assert_eq!(Statistic::Max.of(&odd), Some(5.0));
+
+        let even = [4.0, 1.0, 3.0, 100.0];
+        assert_eq!(Statistic::Median.of(&even), Some(3.5));
+        assert_eq!(Statistic::Min.of(&even), Some(1.0));
+        assert_eq!(Statistic::Max.of(&even), Some(100.0));
+
+        assert_eq!(Statistic::Median.of(&[]), None);
+    }
+
+    #[test]
+    fn statistic_falls_back_to_the_score_without_raw_data() {
+        let mut with_raw_data = result("MyBench.a", Mode::AverageTime, 10.0, "ns/op");
+        with_raw_data.raw_data = vec![1.0, 2.0, 30.0];
+        let without = result("MyBench.b", Mode::AverageTime, 10.0, "ns/op");
+        let mut results = vec![with_raw_data, without];
+
+        assert_eq!(use_statistic(&mut results, Statistic::Median), 1);
+        assert_eq!(results[0].score, 2.0);
+        assert_eq!(results[1].score, 10.0);
+    }
+
+    #[test]
+    fn renamed_prefix_restores_the_diffs() {
+        let old = || {
+            vec![
+                result("com.old.Bench.a", Mode::AverageTime, 10.0, "ns/op"),
+                result("com.old.Bench.b", Mode::AverageTime, 20.0, "ns/op"),
+            ]
+        };
+        let new = vec![
+            result("com.new.Bench.a", Mode::AverageTime, 11.0, "ns/op"),
+            result("com.new.Bench.b", Mode::AverageTime, 22.0, "ns/op"),
+        ];
+        assert!(compare_benchmark_results(old(), new.clone())
+            .iter()
+            .all(BenchComparison::is_missing));
+
+        let mut renamed = old();
+        rename_prefixes(
+            &mut renamed,
+            &[
+                ("com.other.".to_string(), "com.elsewhere.".to_string()),
+                ("com.old.".to_string(), "com.new.".to_string()),
+            ],
+        );
+        let comparisons = compare_benchmark_results(renamed, new);
+        assert_eq!(comparisons.len(), 2);
+        assert!(comparisons.iter().all(|c| c.as_changed().is_some()));
+    }
+
+    #[test]
+    fn crlf_input_parses_like_lf_input() {
+        let lf = "\
+# Run complete. Total time: 00:01:00
+
+Benchmark    (size)  Mode  Cnt  Score   Error  Units
+MyBench.a        10  avgt    5  1.000 ± 0.010  ns/op
+MyBench.b        10  ss      1  2.000          ns/op
+
+";
+        let crlf = lf.replace('\n', "\r\n");
+        let (results, errors) = parse_str(&crlf);
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].units, "ns/op");
+        assert_eq!(format!("{results:?}"), format!("{:?}", parse_str(lf).0));
+    }
+
+    #[test]
+    fn diff_styles() {
+        let old = result("MyBench.run", Mode::Throughput, 1000.0, "ops/s");
+        let new = result("MyBench.run", Mode::Throughput, 1200.0, "ops/s");
+        let diff = calculate_delta(&new, &old);
+        let diff_cell = |diff_style| {
+            let format = NumberFormat {
+                diff_style,
+                ..NumberFormat::default()
+            };
+            let column = BenchDiff::headers()
+                .iter()
+                .position(|h| h == "diff")
+                .unwrap();
+            diff.cells(&format)[column].to_string()
+        };
+        assert_eq!(diff_cell(DiffStyle::Ratio), "+20.00000%");
+        assert_eq!(diff_cell(DiffStyle::Absolute), "+200 ops/s");
+        assert_eq!(diff_cell(DiffStyle::Both), "+200 ops/s (+20.00000%)");
+    }
+
+    #[test]
+    fn trailing_blank_lines_keep_the_last_block() {
+        let headerless = "\
+# Run complete
+
+MyBench.a    avgt    5  1.000 ± 0.010  ns/op
+MyBench.b    avgt    5  2.000 ± 0.010  ns/op
+
+
+";
+        let headered = "\
+Benchmark    Mode  Cnt  Score   Error  Units
+MyBench.a    avgt    5  1.000 ± 0.010  ns/op
+MyBench.b    avgt    5  2.000 ± 0.010  ns/op
+
+
+";
+        for input in [headerless, headered] {
+            assert!(input.ends_with("\n\n\n"));
+            let (results, errors) = parse_str(input);
+            assert!(errors.is_empty(), "{errors:?}");
+            assert_eq!(results.len(), 2);
+        }
+    }
+
+    #[test]
+    fn mode_aliases() {
+        let aliases = [
+            ("thrpt", Mode::Throughput),
+            ("throughput", Mode::Throughput),
+            ("Throughput", Mode::Throughput),
+            ("avgt", Mode::AverageTime),
+            ("average", Mode::AverageTime),
+            ("averagetime", Mode::AverageTime),
+            ("AverageTime", Mode::AverageTime),
+            ("sample", Mode::SampleTime),
+            ("sampletime", Mode::SampleTime),
+            ("SampleTime", Mode::SampleTime),
+            ("ss", Mode::SingleShotTime),
+            ("singleshot", Mode::SingleShotTime),
+            ("singleshottime", Mode::SingleShotTime),
+            ("SingleShotTime", Mode::SingleShotTime),
+        ];
+        for (alias, mode) in aliases {
+            assert_eq!(alias.parse::<Mode>(), Ok(mode), "{alias}");
+        }
+        assert_eq!(
+            "fast".parse::<Mode>(),
+            Err("unknown mode 'fast'".to_string())
+        );
+
+        let short: Vec<String> = [
+            Mode::Throughput,
+            Mode::AverageTime,
+            Mode::SampleTime,
+            Mode::SingleShotTime,
+        ]
+        .iter()
+        .map(Mode::to_string)
+        .collect();
+        assert_eq!(short, ["thrpt", "avgt", "sample", "ss"]);
+    }
+
+    #[test]
+    fn exact_patterns_beat_wildcards() {
+        let mut thresholds = Thresholds::new(Some(5.0));
+        thresholds.add("com.example.MyBench.hot*", 10.0);
+        thresholds.add("MyBench.hotPath", 2.0);
+        let hot = result(
+            "com.example.MyBench.hotPath",
+            Mode::AverageTime,
+            1.0,
+            "ns/op",
+        );
+        let other = result(
+            "com.example.MyBench.hotLoop",
+            Mode::AverageTime,
+            1.0,
+            "ns/op",
+        );
+        assert_eq!(thresholds.get(&calculate_delta(&hot, &hot)), 2.0);
+        assert_eq!(thresholds.get(&calculate_delta(&other, &other)), 10.0);
+    }
+
+    #[test]
+    fn more_specific_patterns_beat_less_specific_ones() {
+        let mut thresholds = Thresholds::new(Some(5.0));
+        thresholds.add("com.example.slow.*", 20.0);
+        thresholds.add("com.example.*", 15.0);
+        let slow = result(
+            "com.example.slow.Bench.run",
+            Mode::AverageTime,
+            1.0,
+            "ns/op",
+        );
+        let fast = result(
+            "com.example.fast.Bench.run",
+            Mode::AverageTime,
+            1.0,
+            "ns/op",
+        );
+        assert_eq!(thresholds.get(&calculate_delta(&slow, &slow)), 20.0);
+        assert_eq!(thresholds.get(&calculate_delta(&fast, &fast)), 15.0);
+    }
+
+    #[test]
+    fn benchmarks_no_pattern_matches_use_the_default() {
+        let mut thresholds = Thresholds::new(Some(5.0));
+        thresholds.add("MyBench.hotPath", 2.0);
+        let other = result("com.example.Other.run", Mode::AverageTime, 1.0, "ns/op");
+        let diff = calculate_delta(&other, &other);
+        assert_eq!(thresholds.get(&diff), 5.0);
+
+        // A pattern only matches whole parts of the name
+        let partial = result(
+            "com.example.NotMyBench.hotPath",
+            Mode::AverageTime,
+            1.0,
+            "ns/op",
+        );
+        assert_eq!(thresholds.get(&calculate_delta(&partial, &partial)), 5.0);
+
+        thresholds.default = None;
+        assert_eq!(thresholds.get(&diff), Mode::AverageTime.default_threshold());
+    }
+
+    #[test]
+    fn thresholds_from_toml() {
+        let mut thresholds = Thresholds::default();
+        let input = r#"
+"MyBench.hotPath" = 2.0
+
+[thresholds]
+"com.example.slow.*" = 15
+"#;
+        thresholds.parse_toml(input).unwrap();
+        let hot = result(
+            "com.example.MyBench.hotPath",
+            Mode::AverageTime,
+            1.0,
+            "ns/op",
+        );
+        let slow = result(
+            "com.example.slow.Bench.run",
+            Mode::AverageTime,
+            1.0,
+            "ns/op",
+        );
+        assert_eq!(thresholds.get(&calculate_delta(&hot, &hot)), 2.0);
+        assert_eq!(thresholds.get(&calculate_delta(&slow, &slow)), 15.0);
+
+        assert_eq!(
+            Thresholds::default().parse_toml("\"MyBench.run\" = \"fast\"\n"),
+            Err("the threshold for 'MyBench.run' should be a number".to_string())
+        );
+        assert_eq!(
+            Thresholds::default().parse_toml("[limits]\n\"MyBench.run\" = 2\n"),
+            Err("the threshold for 'limits' should be a number".to_string())
+        );
+    }
+
+    #[test]
+    fn absolute_threshold_near_zero() {
+        // Doubling is a huge percentage, but only a thousandth of a nanosecond
+        let old = result("MyBench.noop", Mode::AverageTime, 0.001, "ns/op");
+        let new = result("MyBench.noop", Mode::AverageTime, 0.002, "ns/op");
+        let diff = calculate_delta(&new, &old);
+
+        let mut thresholds = Thresholds::new(Some(5.0));
+        assert!(thresholds.exceeded_by(&diff));
+
+        thresholds.absolute = Some(0.5);
+        assert!(!thresholds.exceeded_by(&diff));
+        assert_eq!(thresholds.describe(&diff), "5% and 0.5 ns/op");
+
+        thresholds.logic = ThresholdLogic::Or;
+        assert!(thresholds.exceeded_by(&diff));
+        assert_eq!(thresholds.describe(&diff), "5% or 0.5 ns/op");
+    }
+
+    #[test]
+    fn absolute_threshold_is_in_the_new_units() {
+        // 1 us/op to 2000 ns/op is a regression of 1000 ns/op
+        let old = result("MyBench.run", Mode::AverageTime, 1.0, "us/op");
+        let new = result("MyBench.run", Mode::AverageTime, 2000.0, "ns/op");
+        let diff = calculate_delta(&new, &old);
+
+        let mut thresholds = Thresholds::new(Some(5.0));
+        thresholds.absolute = Some(999.0);
+        assert!(thresholds.exceeded_by(&diff));
+        thresholds.absolute = Some(1001.0);
+        assert!(!thresholds.exceeded_by(&diff));
+    }
+
+    #[test]
+    fn thousands_separators() {
+        use DecimalSeparator::{Comma, Point};
+        assert_eq!(parse_score("1,234.5", Point), Some(1234.5));
+        assert_eq!(parse_score("1.234,5", Comma), Some(1234.5));
+        assert_eq!(parse_score("-1,234,567", Point), Some(-1234567.0));
+        assert_eq!(parse_score("1234,5", Comma), Some(1234.5));
+        assert_eq!(parse_score("1234.5", Point), Some(1234.5));
+
+        // A separator that can't be between thousands is a decimal point of the other locale
+        assert_eq!(parse_score("1,5", Point), None);
+        assert_eq!(parse_score("12,34.5", Point), None);
+        assert_eq!(parse_score("1.5", Comma), None);
+    }
+
+    #[test]
+    fn rows_with_a_decimal_comma() {
+        let input = "\
+Benchmark    Mode  Cnt     Score   Error  Units
+MyBench.a   thrpt    5  1.234,5 ± 0,010  ops/s
+";
+        let (results, errors) =
+            parse_reader_with(input.as_bytes(), DecimalSeparator::Comma).unwrap();
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(results[0].score, 1234.5);
+        assert_eq!(results[0].error, 0.01);
+
+        let (results, _) = parse_str(&input.replace("1.234,5 ± 0,010", "1,234.5 ± 0.010"));
+        assert_eq!(results[0].score, 1234.5);
+    }
+
+    #[test]
+    fn mode_sets_of_files_with_different_modes() {
+        let old = vec![
+            result("MyBench.a", Mode::Throughput, 10.0, "ops/s"),
+            result("MyBench.b", Mode::Throughput, 10.0, "ops/s"),
+        ];
+        let new = vec![
+            result("MyBench.a", Mode::AverageTime, 0.1, "s/op"),
+            result("MyBench.b", Mode::SampleTime, 0.1, "s/op"),
+            result("MyBench.c", Mode::AverageTime, 0.1, "s/op"),
+        ];
+        assert_eq!(modes_of(&old), [Mode::Throughput]);
+        assert_eq!(modes_of(&new), [Mode::AverageTime, Mode::SampleTime]);
+        assert_ne!(modes_of(&old), modes_of(&new));
+        assert!(modes_of(&[]).is_empty());
+    }
+
+    #[test]
+    fn short_names_keep_the_secondary_metric() {
+        let mut names = [
+            "com.example.A.run:·gc.alloc.rate.norm[size=10]".to_string(),
+            "com.example.B.run:·gc.alloc.rate.norm".to_string(),
+        ];
+        shorten_names(names.iter_mut());
+        assert_eq!(
+            names,
+            [
+                "A.run:·gc.alloc.rate.norm[size=10]",
+                "B.run:·gc.alloc.rate.norm"
+            ]
+        );
+
+        let mut names = ["com.example.A.run:·gc.alloc.rate.norm".to_string()];
+        shorten_names(names.iter_mut());
+        assert_eq!(names, ["A.run:·gc.alloc.rate.norm"]);
+    }
+
+    #[test]
+    fn environment_is_read_up_to_the_first_benchmark() {
+        // Anything past the first benchmark would be an error to read
+        let text = b"# JMH version: 1.37\n# VM version: JDK 17.0.2, OpenJDK 64-Bit Server VM, 17.0.2+8\n# VM invoker: /usr/bin/java\n# VM options: -Xmx2g -XX:+UseG1GC\n# Benchmark: a.b\n\xff\n";
+        let environment = read_environment(&text[..]).unwrap().unwrap();
+        assert_eq!(environment.jmh_version, JmhVersion::parse("1.37"));
+        assert_eq!(
+            environment.vm.as_deref(),
+            Some("JDK 17.0.2, OpenJDK 64-Bit Server VM, 17.0.2+8")
+        );
+        assert_eq!(environment.invoker.as_deref(), Some("/usr/bin/java"));
+        assert_eq!(environment.options, ["-Xmx2g", "-XX:+UseG1GC"]);
+
+        let json = b"[\n  {\n    \"jmhVersion\": \"1.37\",\n    \"benchmark\": \"a.b\",\n    \"primaryMetric\": {\"score\": 1, \"scoreUnit\": \"ns/op\"},\n    \"jdkVersion\": \"17.0.2\",\n    \"vmName\": \"OpenJDK 64-Bit Server VM\",\n    \"vmVersion\": \"17.0.2+8\",\n    \"jvm\": \"C:\\\\java\\\\bin\\\\java.exe\",\n    \"jvmArgs\": [\n      \"-Xmx2g\",\n      \"-Dname={x}\"\n    ]\n  },\n\xff\n";
+        let environment = read_environment(&json[..]).unwrap().unwrap();
+        assert_eq!(environment.jmh_version, JmhVersion::parse("1.37"));
+        assert_eq!(
+            environment.vm.as_deref(),
+            Some("JDK 17.0.2, OpenJDK 64-Bit Server VM, 17.0.2+8")
+        );
+        assert_eq!(
+            environment.invoker.as_deref(),
+            Some(r"C:\java\bin\java.exe")
+        );
+        assert_eq!(environment.options, ["-Xmx2g", "-Dname={x}"]);
+
+        let table =
+            "Benchmark  Mode  Cnt  Score   Error  Units\na.b        avgt    5  1.0 ± 0.1  ns/op\n";
+        assert_eq!(read_environment(table.as_bytes()).unwrap(), None);
+    }
+
+    #[test]
+    fn annotations_give_the_size_of_the_change() {
+        let faster = calculate_delta(
+            &result("MyBench.time", Mode::AverageTime, 9.0, "ns/op"),
+            &result("MyBench.time", Mode::AverageTime, 10.0, "ns/op"),
+        );
+        let slower = calculate_delta(
+            &result("MyBench.ops", Mode::Throughput, 9.0, "ops/s"),
+            &result("MyBench.ops", Mode::Throughput, 10.0, "ops/s"),
+        );
+        assert_eq!(
+            github_annotations([&faster, &slower], &Thresholds::new(Some(5.0))),
+            [
+                "::notice title=Benchmark improvement::MyBench.time improved by 10.00000%25",
+                "::error title=Benchmark regression::MyBench.ops regressed by 10.00000%25",
+            ]
+        );
+    }
+
+    #[test]
+    fn percent_has_no_negative_zero() {
+        assert_eq!(percent(-0.0), "+0.00%");
+        assert_eq!(percent(-0.00001), "+0.00%");
+        assert_eq!(percent(-0.0001), "-0.01%");
+        assert_eq!(percent(0.00001), "+0.00%");
+    }
+
+    #[test]
+    fn results_display_as_a_jmh_row() {
+        let mut with_error = result("MyBench.run", Mode::AverageTime, 1.23, "ns/op");
+        with_error.error = 0.01;
+        assert_eq!(
+            with_error.to_string(),
+            "MyBench.run  avgt  5  1.230 ± 0.010  ns/op"
+        );
+        assert_eq!(
+            with_error.to_string().parse::<BenchResult>(),
+            Ok(with_error)
+        );
+
+        let mut single_shot = result("MyBench.once", Mode::SingleShotTime, 2.0, "ms/op");
+        single_shot.count = 1;
+        assert_eq!(single_shot.to_string(), "MyBench.once  ss  1  2.000  ms/op");
+        assert_eq!(
+            single_shot.to_string().parse::<BenchResult>(),
+            Ok(single_shot)
+        );
+    }
+
+    #[test]
+    fn jmh_tables_parse_back_into_the_same_results() {
+        let (results, errors) = parse_str(include_str!("../tests/data/old.txt"));
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(results.len(), 5);
+        let (parsed, errors) = parse_str(&to_jmh_table(&results));
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(parsed, results);
+    }
+
+    #[test]
+    fn jmh_tables_with_params_parse_back_into_the_same_results() {
+        let input = "\
+Benchmark              (kind)  (size)    Mode  Cnt     Score    Error  Units
+MyBench.run               a b      10   thrpt   25  1234.567 ± 12.345  ops/s
+MyBench.run            single     100    avgt    5     0.001 ±  0.001  ms/op
+MyBench.other             N/A    1000      ss    1    42.000           us/op
+";
+        let (results, errors) = parse_str(input);
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results[2].params,
+            [("size".to_string(), "1000".to_string())]
+        );
+        let (parsed, errors) = parse_str(&to_jmh_table(&results));
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(parsed, results);
+    }
+
+    /// Lines made of the tokens of JMH rows, which get further into the parser than random text.
+    fn row_like() -> impl Strategy<Value = String> {
+        let token = prop_oneof![
+            Just("MyBench.run".to_string()),
+            Just("thrpt".to_string()),
+            Just("avgt".to_string()),
+            Just("±".to_string()),
+            Just("N/A".to_string()),
+            Just("NaN".to_string()),
+            Just("∞".to_string()),
+            Just("ops/s".to_string()),
+            Just("1,234.5".to_string()),
+            "[0-9.,+-]{0,8}",
+            "\\PC{0,3}",
+        ];
+        proptest::collection::vec((token, "[ \t]{0,3}"), 0..10)
+            .prop_map(|parts| parts.into_iter().map(|(t, s)| t + &s).collect())
+    }
+
+    proptest! {
+        #[test]
+        fn parse_row_never_panics(line in prop_oneof![row_like(), any::<String>()]) {
+            let _ = parse_row(&line, 1);
+        }
+
+        #[test]
+        fn parse_row_with_params_never_panics(
+            line in prop_oneof![row_like(), any::<String>()],
+            ends in proptest::collection::vec(0..40usize, 0..3),
+        ) {
+            let columns: Vec<ParamColumn> = ends
+                .into_iter()
+                .map(|end| ParamColumn { name: "size".to_string(), end })
+                .collect();
+            let _ = parse_row_with_params(&line, 1, &columns);
+        }
+    }
+}
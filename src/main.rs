@@ -16,262 +16,2168 @@
 
 use std::{
     borrow::Cow,
+    collections::HashSet,
     env,
-    fmt::Display,
-    fmt::{Formatter, Result as FmtResult},
+    fmt::{Display, Formatter, Result as FmtResult},
+    fs::{self, File},
+    io::{self, BufWriter, IsTerminal, Write},
+    panic,
     path::Path,
     process,
     str::FromStr,
+    thread,
+    time::Instant,
 };
 
-use tabled::{settings::Style, Table, Tabled};
+use jmhcmp::{
+    class_heading, compare_benchmark_results_by, compare_trend, count_headers, find_mode_changes,
+    github_annotations, group_by_class, has_percentiles, invert_improvements, is_secondary_row,
+    mark_high_variance, mark_noise, match_names_ignoring_case, match_reciprocals, modes_of,
+    parse_file_with, parse_json_file, parse_json_reader, parse_reader_with, read_environment,
+    rename_prefixes, rows_to_markdown, secondary_results, shorten_names, sort_comparisons,
+    sort_trends, thread_headers, to_jmh_table, to_junit, to_markdown, trend_rows, use_statistic,
+    BenchComparison, BenchDiff, BenchResult, BenchTrend, DecimalSeparator, Diagnostics, DiffStyle,
+    MatchKey, Mode, NumberFormat, ParseError, RunEnvironment, SortOrder, Statistic, Summary,
+    ThresholdLogic, Thresholds, ID_PARAM, P99_DIFF_HEADER,
+};
+use regex::Regex;
+use serde_json::Value;
+use tabled::{
+    builder::Builder,
+    settings::{object::Cell, Color, Modify, Style},
+    Table, Tabled,
+};
+
+/// The path that can be passed instead of a file to read from stdin.
+const STDIN_PATH: &str = "-";
 
 #[derive(Debug, Clone, Copy)]
-enum Mode {
-    AverageTime,
-    SampleTime,
-    SingleShotTime,
-    Throughput,
+enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+    Markdown,
+    Junit,
+    Github,
+    Line,
 }
 
-#[derive(Debug)]
-enum ParseError {
-    InvalidMode,
-    InvalidFloat,
-    InvalidInt,
-    MissingName,
-    MissingCount,
+#[derive(Debug, Clone, Copy)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
 }
 
-#[derive(Debug, Tabled)]
-struct BenchResult {
-    name: String,
-    mode: Mode,
-    count: i64,
-    score: f64,
-    error: f64,
-    units: String,
+#[derive(Debug, Clone, Copy)]
+enum TableStyle {
+    Blank,
+    Ascii,
+    Modern,
+    Rounded,
+    Sharp,
+    Psql,
+    Markdown,
+}
+
+impl TableStyle {
+    fn apply(self, table: &mut Table) {
+        match self {
+            Self::Blank => table.with(Style::blank()),
+            Self::Ascii => table.with(Style::ascii()),
+            Self::Modern => table.with(Style::modern()),
+            Self::Rounded => table.with(Style::rounded()),
+            Self::Sharp => table.with(Style::sharp()),
+            Self::Psql => table.with(Style::psql()),
+            Self::Markdown => table.with(Style::markdown()),
+        };
+    }
 }
 
 #[derive(Debug)]
-struct BenchDiff {
-    name: String,
-    mode: Mode,
-    old_score: f64,
-    new_score: f64,
-    units: String,
-    diff: f64,
+enum RunError {
+    OldFile(io::Error),
+    NewFile(io::Error),
+    File { path: String, error: io::Error },
+    Output(io::Error),
+    Parse { count: usize },
+    Empty { path: String },
+    Regression { count: usize },
+    InsufficientImprovement { count: usize },
+    Config { path: String, error: String },
 }
 
 #[derive(Debug)]
 struct Config {
     new_file: String,
     old_file: String,
+    /// Files between the old and new ones when showing a trend over several runs.
+    middle_files: Vec<String>,
+    thresholds: ThresholdOptions,
+    filters: Filters,
+    output: OutputOptions,
+    /// The old file was given with --baseline-json, so it's always parsed as JSON.
+    baseline_json: bool,
+    /// The config file to read, instead of jmhcmp.toml if it exists.
+    config_file: Option<String>,
+    /// What the old and new results are matched on.
+    match_key: MatchKey,
+    /// Only show the new results, without comparing them to the old file.
+    baseline: bool,
+    /// Only show the results of every file, to check how they were parsed.
+    list: bool,
+    /// Print the results of the new file as a JMH table, to check that they parse back the
+    /// same way. Not in the usage, since it's for testing the parser.
+    round_trip: bool,
+    /// Fail instead of skipping rows that can't be parsed.
+    strict: bool,
+    /// Warn about benchmarks whose warmup scored more than this percentage away from the score.
+    warmup_check: Option<f64>,
+    /// How to read the scores of text and CSV files written in another locale.
+    decimal_separator: DecimalSeparator,
+    /// Report the largest diff as the noise floor, for comparing two runs of the same code.
+    self_variance: bool,
+    /// Print how long parsing and comparing took to stderr.
+    timing: bool,
+    /// Compare throughput against average time results of the same benchmark.
+    allow_reciprocal: bool,
+    /// A secondary metric, like `gc.alloc.rate.norm`, to compare in a table of its own.
+    secondary: Option<String>,
+    ignore_case: bool,
+    /// Count lower throughput and higher times as improvements.
+    invert: bool,
+    /// Old and new name prefixes, applied to all but the new results.
+    renames: Vec<(String, String)>,
+    /// The statistic of the raw data in JSON files to compare instead of the reported mean.
+    statistic: Statistic,
 }
 
-impl BenchDiff {
-    fn diff_str(&self) -> String {
-        format!("{:+.5}%", self.diff * 100.0)
+/// When a change fails the run, warns or doesn't count at all.
+#[derive(Debug)]
+struct ThresholdOptions {
+    /// The regression thresholds, from --threshold and the config file.
+    fail: Thresholds,
+    /// Regressions of more than this are reported without failing the run.
+    warn: Option<f64>,
+    /// Diffs smaller than this percentage are shown as unchanged.
+    noise: Option<f64>,
+    /// Diffs are marked as high variance if an error is more than this percentage of its score.
+    max_rsd: f64,
+    /// The percentage all benchmarks, or the named one, have to improve by.
+    required_improvements: Vec<(Option<String>, f64)>,
+}
+
+/// Which benchmarks are compared.
+#[derive(Debug, Default)]
+struct Filters {
+    /// Benchmarks matching any of these are compared, or all of them if there are none.
+    patterns: Vec<Regex>,
+    /// The names of the only benchmarks to compare, from --include-file.
+    include: Option<HashSet<String>>,
+    /// The names of benchmarks not to compare, from --exclude-file.
+    exclude: HashSet<String>,
+}
+
+/// How the comparison is printed.
+#[derive(Debug)]
+struct OutputOptions {
+    format: OutputFormat,
+    /// The file to write to instead of stdout.
+    file: Option<String>,
+    color: ColorChoice,
+    style: TableStyle,
+    number_format: NumberFormat,
+    sort: SortOrder,
+    hide_missing: bool,
+    only_regressions: bool,
+    short_names: bool,
+    /// Add columns with the old and new iteration counts.
+    show_counts: bool,
+    /// Add columns with the old and new thread counts.
+    show_threads: bool,
+    /// Print the JVM each file was run on.
+    show_env: bool,
+    /// Show the benchmarks as the columns of the table, and the columns as its rows.
+    transpose: bool,
+    /// Only show this many rows in the formats meant for people.
+    max_rows: Option<usize>,
+    /// Print a single line instead of the table when nothing changed by more than its threshold.
+    quiet: bool,
+    /// Keep the benchmarks of each class together, under a heading with their geomean.
+    group_by_class: bool,
+    /// Indent the JSON output instead of writing it on one line.
+    json_pretty: bool,
+    /// Names for the old and new results in the column headers.
+    old_label: String,
+    new_label: String,
+}
+
+impl Default for ThresholdOptions {
+    fn default() -> Self {
+        Self {
+            fail: Thresholds::default(),
+            warn: None,
+            noise: None,
+            max_rsd: DEFAULT_MAX_RSD,
+            required_improvements: vec![],
+        }
+    }
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self {
+            format: OutputFormat::Table,
+            file: None,
+            color: ColorChoice::Auto,
+            style: TableStyle::Blank,
+            number_format: NumberFormat::default(),
+            sort: SortOrder::None,
+            hide_missing: false,
+            only_regressions: false,
+            short_names: false,
+            show_counts: false,
+            show_threads: false,
+            show_env: false,
+            transpose: false,
+            max_rows: None,
+            quiet: false,
+            group_by_class: false,
+            json_pretty: false,
+            old_label: "old".to_string(),
+            new_label: "new".to_string(),
+        }
     }
 }
 
-impl Tabled for BenchDiff {
-    const LENGTH: usize = 6;
+impl Config {
+    /// Returns the diff between the first and last run of each trend.
+    fn trend_diffs(&self, trends: &[BenchTrend]) -> Vec<BenchDiff> {
+        let mut diffs: Vec<BenchDiff> = trends.iter().filter_map(BenchTrend::diff).collect();
+        for diff in &mut diffs {
+            diff.inverted = self.invert;
+        }
+        diffs
+    }
+
+    fn headers(&self) -> Vec<Cow<'static, str>> {
+        let mut headers =
+            BenchDiff::headers_with_labels(&self.output.old_label, &self.output.new_label);
+        if self.output.show_counts {
+            headers.extend(count_headers(
+                &self.output.old_label,
+                &self.output.new_label,
+            ));
+        }
+        if self.output.show_threads {
+            headers.extend(thread_headers(
+                &self.output.old_label,
+                &self.output.new_label,
+            ));
+        }
+        headers
+    }
 
-    /// Fields method must return a list of cells.
-    ///
-    /// The cells will be placed in the same row, preserving the order.
-    fn fields(&self) -> Vec<Cow<'_, str>> {
-        vec![
-            Cow::Owned(self.name.to_string()),
-            Cow::Owned(self.mode.to_string()),
-            Cow::Owned(self.old_score.to_string()),
-            Cow::Owned(self.new_score.to_string()),
-            Cow::Owned(self.units.to_string()),
-            Cow::Owned(self.diff_str().to_string()),
-        ]
+    /// Returns true if names should be shortened, which is never done for the formats meant for
+    /// other tools.
+    fn shorten_names(&self) -> bool {
+        self.output.short_names
+            && !matches!(self.output.format, OutputFormat::Json | OutputFormat::Junit)
     }
-    /// Headers must return a list of column names.
-    fn headers() -> Vec<Cow<'static, str>> {
-        vec![
-            Cow::Owned("name".to_string()),
-            Cow::Owned("mode".to_string()),
-            Cow::Owned("old count".to_string()),
-            Cow::Owned("new count".to_string()),
-            Cow::Owned("units".to_string()),
-            Cow::Owned("diff".to_string()),
-        ]
+
+    /// Returns the time a step started at if --timing was given, so it can be reported.
+    fn start_timer(&self) -> Option<Instant> {
+        self.timing.then(Instant::now)
+    }
+
+    /// Writes JSON output, indented if --json-pretty was given.
+    fn write_json(&self, out: &mut impl Write, json: Vec<Value>) -> io::Result<()> {
+        let json = Value::Array(json);
+        if self.output.json_pretty {
+            writeln!(out, "{json:#}")
+        } else {
+            writeln!(out, "{json}")
+        }
+    }
+
+    /// Returns true if the output is meant to be read by people rather than other tools.
+    fn is_human_readable(&self) -> bool {
+        matches!(
+            self.output.format,
+            OutputFormat::Table
+                | OutputFormat::Markdown
+                | OutputFormat::Github
+                | OutputFormat::Line
+        )
+    }
+}
+
+const FORMATS: &[(&str, OutputFormat)] = &[
+    ("table", OutputFormat::Table),
+    ("json", OutputFormat::Json),
+    ("csv", OutputFormat::Csv),
+    ("markdown", OutputFormat::Markdown),
+    ("junit", OutputFormat::Junit),
+    ("github", OutputFormat::Github),
+    ("line", OutputFormat::Line),
+];
+
+const SORT_ORDERS: &[(&str, SortOrder)] = &[
+    ("none", SortOrder::None),
+    ("name", SortOrder::Name),
+    ("diff", SortOrder::Diff),
+];
+
+const STATISTICS: &[(&str, Statistic)] = &[
+    ("mean", Statistic::Mean),
+    ("median", Statistic::Median),
+    ("min", Statistic::Min),
+    ("max", Statistic::Max),
+];
+
+const THRESHOLD_LOGICS: &[(&str, ThresholdLogic)] =
+    &[("and", ThresholdLogic::And), ("or", ThresholdLogic::Or)];
+
+const DIFF_STYLES: &[(&str, DiffStyle)] = &[
+    ("ratio", DiffStyle::Ratio),
+    ("absolute", DiffStyle::Absolute),
+    ("both", DiffStyle::Both),
+];
+
+const COLOR_CHOICES: &[(&str, ColorChoice)] = &[
+    ("auto", ColorChoice::Auto),
+    ("always", ColorChoice::Always),
+    ("never", ColorChoice::Never),
+];
+
+const TABLE_STYLES: &[(&str, TableStyle)] = &[
+    ("blank", TableStyle::Blank),
+    ("ascii", TableStyle::Ascii),
+    ("modern", TableStyle::Modern),
+    ("rounded", TableStyle::Rounded),
+    ("sharp", TableStyle::Sharp),
+    ("psql", TableStyle::Psql),
+    ("markdown", TableStyle::Markdown),
+];
+
+/// Reads the values of flags from the command line, given either as `--flag value` or as
+/// `--flag=value`.
+struct Args<I> {
+    args: I,
+    /// The value of the last flag, if it was given after an `=`.
+    inline_value: Option<String>,
+}
+
+impl<I: Iterator<Item = String>> Args<I> {
+    /// Returns the next flag or positional argument.
+    fn next_arg(&mut self) -> Option<String> {
+        let arg = self.args.next()?;
+        match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => {
+                self.inline_value = Some(value.to_string());
+                Some(flag.to_string())
+            }
+            _ => Some(arg),
+        }
+    }
+
+    fn value(&mut self, flag: &str) -> Result<String, String> {
+        self.inline_value
+            .take()
+            .or_else(|| self.args.next())
+            .ok_or_else(|| format!("Didn't get a value for {flag}"))
+    }
+
+    fn parse<T: FromStr>(&mut self, flag: &str) -> Result<T, String> {
+        self.value(flag)?
+            .parse()
+            .map_err(|_| format!("Invalid value for {flag}"))
+    }
+
+    fn choice<T: Copy>(&mut self, flag: &str, choices: &[(&str, T)]) -> Result<T, String> {
+        let value = self.value(flag)?;
+        match choices.iter().find(|(name, _)| *name == value) {
+            Some(&(_, choice)) => Ok(choice),
+            None => {
+                let names: Vec<&str> = choices.iter().map(|&(name, _)| name).collect();
+                let (last, rest) = names.split_last().unwrap_or((&"", &[]));
+                Err(format!(
+                    "Invalid value for {flag}, expected {} or {last}",
+                    rest.join(", ")
+                ))
+            }
+        }
+    }
+
+    /// Fails if a flag that doesn't take a value was given one after an `=`.
+    fn no_value(&mut self, flag: &str) -> Result<(), String> {
+        match self.inline_value.take() {
+            Some(_) => Err(format!("{flag} doesn't take a value")),
+            None => Ok(()),
+        }
+    }
+}
+
+impl ThresholdOptions {
+    /// Reads a threshold flag and its value, returning false if `flag` isn't one.
+    fn parse_flag<I: Iterator<Item = String>>(
+        &mut self,
+        flag: &str,
+        args: &mut Args<I>,
+    ) -> Result<bool, String> {
+        match flag {
+            "--threshold" | "--fail-threshold" => self.fail.default = Some(args.parse(flag)?),
+            "--warn-threshold" => self.warn = Some(args.parse(flag)?),
+            "--abs-threshold" => self.fail.absolute = Some(args.parse(flag)?),
+            "--threshold-logic" => self.fail.logic = args.choice(flag, THRESHOLD_LOGICS)?,
+            "--fail-under-improvement" => {
+                let value = args.value(flag)?;
+                let (name, percent) = match value.rsplit_once('=') {
+                    Some((name, percent)) => (Some(name.to_string()), percent),
+                    None => (None, value.as_str()),
+                };
+                match percent.parse() {
+                    Ok(percent) => self.required_improvements.push((name, percent)),
+                    Err(_) => return Err(format!("Invalid value for {flag}: {value}")),
+                }
+            }
+            "--noise" => self.noise = Some(args.parse(flag)?),
+            "--max-rsd" => self.max_rsd = args.parse(flag)?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+impl Filters {
+    /// Returns true if the benchmark passes the filter and the include and exclude lists.
+    fn is_selected(&self, result: &BenchResult) -> bool {
+        (self.patterns.is_empty() || self.patterns.iter().any(|f| f.is_match(&result.name)))
+            && self
+                .include
+                .as_ref()
+                .is_none_or(|i| i.contains(&result.name))
+            && !self.exclude.contains(&result.name)
+    }
+
+    /// Reads a filter flag and its value, returning false if `flag` isn't one.
+    fn parse_flag<I: Iterator<Item = String>>(
+        &mut self,
+        flag: &str,
+        args: &mut Args<I>,
+    ) -> Result<bool, String> {
+        match flag {
+            "--filter" => {
+                let value = args.value(flag)?;
+                match Regex::new(&value) {
+                    Ok(re) => self.patterns.push(re),
+                    Err(_) => return Err("Invalid regex for --filter".to_string()),
+                }
+            }
+            "--include-file" => self
+                .include
+                .get_or_insert_with(HashSet::new)
+                .extend(read_name_list(&args.value(flag)?)?),
+            "--exclude-file" => self.exclude.extend(read_name_list(&args.value(flag)?)?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+impl OutputOptions {
+    /// Reads an output flag and its value, returning false if `flag` isn't one.
+    fn parse_flag<I: Iterator<Item = String>>(
+        &mut self,
+        flag: &str,
+        args: &mut Args<I>,
+    ) -> Result<bool, String> {
+        match flag {
+            "--format" => self.format = args.choice(flag, FORMATS)?,
+            "--sort" => self.sort = args.choice(flag, SORT_ORDERS)?,
+            "--color" => self.color = args.choice(flag, COLOR_CHOICES)?,
+            "--style" => self.style = args.choice(flag, TABLE_STYLES)?,
+            "--old-label" => self.old_label = args.value(flag)?,
+            "--new-label" => self.new_label = args.value(flag)?,
+            "--output" => self.file = Some(args.value(flag)?),
+            "--precision" => self.number_format.precision = Some(args.parse(flag)?),
+            "--hide-missing" => self.hide_missing = true,
+            "--show-counts" => self.show_counts = true,
+            "--show-threads" => self.show_threads = true,
+            "--show-env" => self.show_env = true,
+            "--si" => self.number_format.si_suffixes = true,
+            "--diff-style" => self.number_format.diff_style = args.choice(flag, DIFF_STYLES)?,
+            "--only-regressions" => self.only_regressions = true,
+            "--short-names" => self.short_names = true,
+            "--json-pretty" => self.json_pretty = true,
+            "--group-by-class" => self.group_by_class = true,
+            "--quiet" => self.quiet = true,
+            "--transpose" => self.transpose = true,
+            "--max-rows" => self.max_rows = Some(args.parse(flag)?),
+            _ => return Ok(false),
+        }
+        Ok(true)
     }
 }
 
 impl Config {
-    pub fn build(mut args: impl Iterator<Item = String>) -> Result<Config, &'static str> {
-        args.next();
+    pub fn build(args: impl Iterator<Item = String>) -> Result<Config, String> {
+        let mut args = Args {
+            args,
+            inline_value: None,
+        };
+        args.next_arg();
 
-        let old_file = match args.next() {
+        let mut positional = vec![];
+        let mut thresholds = ThresholdOptions::default();
+        let mut filters = Filters::default();
+        let mut output = OutputOptions::default();
+        let mut baseline_json = None;
+        let mut key_field = None;
+        let mut key_param = None;
+        let mut config_file = None;
+        let mut baseline = false;
+        let mut list = false;
+        let mut strict = false;
+        let mut timing = false;
+        let mut self_variance = false;
+        let mut decimal_separator = DecimalSeparator::Point;
+        let mut warmup_check = None;
+        let mut round_trip = false;
+        let mut allow_reciprocal = false;
+        let mut secondary = None;
+        let mut ignore_case = false;
+        let mut invert = false;
+        let mut statistic = Statistic::Mean;
+        let mut renames = vec![];
+
+        while let Some(arg) = args.next_arg() {
+            let parsed = thresholds.parse_flag(&arg, &mut args)?
+                || filters.parse_flag(&arg, &mut args)?
+                || output.parse_flag(&arg, &mut args)?;
+            if !parsed {
+                match arg.as_str() {
+                    "--key-field" => key_field = Some(args.value(&arg)?),
+                    "--key-param" => key_param = Some(args.value(&arg)?),
+                    "--metric" => statistic = args.choice(&arg, STATISTICS)?,
+                    "--secondary" => secondary = Some(args.value(&arg)?),
+                    "--config" => config_file = Some(args.value(&arg)?),
+                    "--rename" => {
+                        let value = args.value(&arg)?;
+                        match value.split_once('=') {
+                            Some((old, new)) => renames.push((old.to_string(), new.to_string())),
+                            None => {
+                                return Err("Expected --rename old_prefix=new_prefix".to_string())
+                            }
+                        }
+                    }
+                    "--baseline" => baseline = true,
+                    "--baseline-json" => baseline_json = Some(args.value(&arg)?),
+                    "--round-trip" => {
+                        positional.push(args.value(&arg)?);
+                        round_trip = true;
+                    }
+                    "--list" => list = true,
+                    "--strict" => strict = true,
+                    "--timing" => timing = true,
+                    "--self-variance" => self_variance = true,
+                    "--decimal-comma" => decimal_separator = DecimalSeparator::Comma,
+                    "--warmup-check" => warmup_check = Some(args.parse(&arg)?),
+                    "--allow-reciprocal" => allow_reciprocal = true,
+                    "--ignore-case" => ignore_case = true,
+                    "--invert" => invert = true,
+                    // A misspelled flag would otherwise be taken for a file
+                    _ if arg.starts_with('-') && arg != STDIN_PATH => {
+                        return Err(format!("Unknown option {arg}"))
+                    }
+                    _ => positional.push(arg.clone()),
+                }
+            }
+            args.no_value(&arg)?;
+        }
+
+        if let Some(path) = &baseline_json {
+            positional.insert(0, path.clone());
+        }
+
+        if positional.iter().filter(|p| *p == STDIN_PATH).count() > 1 {
+            return Err("Only one file can be read from stdin".to_string());
+        }
+
+        // The old file isn't read for a baseline, so it can be left out
+        if (baseline || list || round_trip) && positional.len() == 1 {
+            positional.insert(0, String::new());
+        }
+
+        let mut positional = positional.into_iter();
+
+        let old_file = match positional.next() {
             Some(arg) => arg,
-            None => return Err("Didn't get a old_file path"),
+            None => return Err("Didn't get a old_file path".to_string()),
         };
 
-        let new_file = match args.next() {
+        let mut middle_files: Vec<String> = positional.collect();
+
+        let new_file = match middle_files.pop() {
             Some(arg) => arg,
-            None => return Err("Didn't get a new_file path"),
+            None => return Err("Didn't get a new_file path".to_string()),
+        };
+
+        let match_key = match (key_field.as_deref(), key_param) {
+            (None | Some("name"), _) => MatchKey::Name,
+            (Some("id"), _) => MatchKey::Param(ID_PARAM.to_string()),
+            (Some("param"), Some(param)) => MatchKey::Param(param),
+            (Some("param"), None) => {
+                return Err(
+                    "--key-field param needs the name of the parameter with --key-param"
+                        .to_string(),
+                )
+            }
+            (Some(_), _) => {
+                return Err("Invalid value for --key-field, expected name, param or id".to_string())
+            }
         };
 
-        Ok(Config { new_file, old_file })
+        Ok(Config {
+            new_file,
+            old_file,
+            middle_files,
+            thresholds,
+            filters,
+            output,
+            baseline_json: baseline_json.is_some(),
+            config_file,
+            match_key,
+            baseline,
+            list,
+            round_trip,
+            strict,
+            warmup_check,
+            decimal_separator,
+            self_variance,
+            timing,
+            allow_reciprocal,
+            secondary,
+            ignore_case,
+            invert,
+            renames,
+            statistic,
+        })
     }
 }
 
-impl FromStr for Mode {
-    type Err = ParseError;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
-            "thrpt" => Ok(Self::Throughput),
-            "avgt" => Ok(Self::AverageTime),
-            "sample" => Ok(Self::SampleTime),
-            "ss" => Ok(Self::SingleShotTime),
-            _ => Err(ParseError::InvalidMode),
+/// Reads a file with a benchmark name on each line, ignoring blank lines.
+fn read_name_list(path: &str) -> Result<HashSet<String>, String> {
+    let list = fs::read_to_string(path).map_err(|e| format!("Couldn't read {path}: {e}"))?;
+    Ok(list
+        .lines()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// The percentage of its score an error can be before the diff is marked as high variance.
+const DEFAULT_MAX_RSD: f64 = 20.0;
+
+/// The exit status for bad command line arguments.
+const ARGUMENT_ERROR_CODE: i32 = 2;
+
+impl RunError {
+    /// Returns the exit status for the error, so scripts can tell the failures apart.
+    fn exit_code(&self) -> i32 {
+        match self {
+            Self::Regression { .. } | Self::InsufficientImprovement { .. } => 1,
+            Self::OldFile(_)
+            | Self::NewFile(_)
+            | Self::File { .. }
+            | Self::Config { .. }
+            | Self::Output(_) => 3,
+            Self::Parse { .. } | Self::Empty { .. } => 4,
         }
     }
 }
 
-impl Display for Mode {
+impl Display for RunError {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         match self {
-            Self::SampleTime => write!(f, "sample"),
-            Self::Throughput => write!(f, "thrpt"),
-            Self::SingleShotTime => write!(f, "ss"),
-            Self::AverageTime => write!(f, "avgt"),
+            Self::OldFile(e) => write!(f, "Problem parsing old benchmarks file: {e}"),
+            Self::NewFile(e) => write!(f, "Problem parsing new benchmarks file: {e}"),
+            Self::File { path, error } => write!(f, "Problem parsing {path}: {error}"),
+            Self::Output(e) => write!(f, "Problem writing the output: {e}"),
+            Self::Empty { path } => {
+                write!(
+                    f,
+                    "Parsed 0 benchmarks from {path}, failing because of --strict"
+                )
+            }
+            Self::Parse { count } => {
+                write!(
+                    f,
+                    "{count} row(s) couldn't be parsed, failing because of --strict"
+                )
+            }
+            Self::InsufficientImprovement { count } => {
+                write!(
+                    f,
+                    "{count} benchmark(s) didn't improve by as much as required"
+                )
+            }
+            Self::Regression { count } => {
+                write!(
+                    f,
+                    "{count} benchmark(s) regressed by more than their threshold"
+                )
+            }
+            Self::Config { path, error } => write!(f, "Problem reading {path}: {error}"),
         }
     }
 }
 
-fn parse_row(input: &str) -> Result<BenchResult, ParseError> {
-    let mut parts = input.split_whitespace().fuse();
+/// Parses a file, detecting its format unless it has to be JSON.
+fn parse_input(
+    path: &str,
+    separator: DecimalSeparator,
+    json: bool,
+) -> Result<(Vec<BenchResult>, Vec<ParseError>), io::Error> {
+    if path == STDIN_PATH {
+        let stdin = io::stdin().lock();
+        if json {
+            parse_json_reader(stdin)
+        } else {
+            parse_reader_with(stdin, separator)
+        }
+    } else if is_url(path) {
+        let response = ureq::get(path).call().map_err(io::Error::other)?;
+        if json {
+            parse_json_reader(response.into_reader())
+        } else {
+            parse_reader_with(response.into_reader(), separator)
+        }
+    } else if json {
+        parse_json_file(path)
+    } else {
+        parse_file_with(path, separator)
+    }
+}
 
-    let name = parts
-        .next()
-        .ok_or_else(|| ParseError::MissingName)?
-        .to_string();
+/// Warns if the files use different sets of modes, like all thrpt in one and all avgt in the
+/// other, which usually means the harness was configured differently.
+fn warn_if_modes_differ(config: &Config, old_results: &[BenchResult], new_results: &[BenchResult]) {
+    let old_modes = modes_of(old_results);
+    let new_modes = modes_of(new_results);
+    if old_modes.is_empty() || new_modes.is_empty() || old_modes == new_modes {
+        return;
+    }
 
-    let mode = parts
-        .next()
-        .ok_or_else(|| ParseError::InvalidMode)?
-        .parse::<Mode>()?;
+    let list = |modes: &[Mode]| {
+        let modes: Vec<String> = modes.iter().map(Mode::to_string).collect();
+        modes.join(", ")
+    };
+    eprintln!(
+        "The files were run in different modes, {} in {} and {} in {}, check how the harness was configured",
+        list(&old_modes),
+        config.old_file,
+        list(&new_modes),
+        config.new_file
+    );
+}
 
-    let count = parts
-        .next()
-        .ok_or_else(|| ParseError::MissingCount)?
-        .parse::<i64>()
-        .map_err(|_| ParseError::InvalidInt)?;
+/// Warns about the benchmarks that ran with a different number of threads in each file, since
+/// their scores can't be compared.
+fn warn_if_threads_differ(comparisons: &[BenchComparison]) {
+    let diffs = comparisons.iter().filter_map(BenchComparison::as_changed);
+    for diff in diffs.filter(|d| d.old_threads != 0 && d.new_threads != 0) {
+        if diff.old_threads != diff.new_threads {
+            eprintln!(
+                "{} ran with {} thread(s) in the old file and {} in the new one, so the comparison may not be valid",
+                diff.name, diff.old_threads, diff.new_threads
+            );
+        }
+    }
+}
 
-    let score = parts
-        .next()
-        .ok_or_else(|| ParseError::MissingCount)?
-        .parse::<f64>()
-        .map_err(|_| ParseError::InvalidFloat)?;
+/// Reads the JMH version and JVM of the old and new files, for the warnings about them.
+fn read_environments(config: &Config) -> (Option<RunEnvironment>, Option<RunEnvironment>) {
+    let environment = |path: &str| {
+        if path == STDIN_PATH || is_url(path) {
+            return None;
+        }
+        File::open(path).and_then(read_environment).ok().flatten()
+    };
+    (environment(&config.old_file), environment(&config.new_file))
+}
 
-    let error = parts
-        .nth(1)
-        .ok_or_else(|| ParseError::MissingCount)?
-        .parse::<f64>()
-        .map_err(|_| ParseError::InvalidFloat)?;
+/// Warns if the old file was produced by a newer version of JMH than the new one, which
+/// usually means the files were passed the wrong way around.
+fn warn_if_swapped(config: &Config, old: Option<&RunEnvironment>, new: Option<&RunEnvironment>) {
+    let version = |environment: Option<&RunEnvironment>| environment?.jmh_version.clone();
+    if let (Some(old), Some(new)) = (version(old), version(new)) {
+        if old > new {
+            eprintln!(
+                "{} was produced by a newer version of JMH ({old}) than {} ({new}), the files may be swapped",
+                config.old_file, config.new_file
+            );
+        }
+    }
+}
 
-    let units = parts
-        .next()
-        .ok_or_else(|| ParseError::MissingCount)?
-        .to_string();
-
-    Ok(BenchResult {
-        name,
-        mode,
-        count,
-        score,
-        error,
-        units,
-    })
-}
-
-fn parse_block(input: &str) -> (Vec<BenchResult>, Vec<ParseError>) {
-    let mut errors = vec![];
-    let results = input
-        .split_terminator("\n")
-        .enumerate()
-        .filter(|&(i, _)| i > 0)
-        .map(|(_, s)| parse_row(s))
-        .filter_map(|r| r.map_err(|e| errors.push(e)).ok())
-        .collect();
-    (results, errors)
+/// Prints the JVM of each file with --show-env, and warns if they were run on different JVMs
+/// or with different options, since that alone can explain a change in the scores.
+fn check_environments(config: &Config, old: Option<&RunEnvironment>, new: Option<&RunEnvironment>) {
+    if config.output.show_env {
+        for (path, environment) in [(&config.old_file, &old), (&config.new_file, &new)] {
+            let Some(environment) = environment else {
+                eprintln!("{path}: unknown JVM");
+                continue;
+            };
+            eprintln!(
+                "{path}: {}",
+                environment.vm.as_deref().unwrap_or("unknown JVM")
+            );
+            if let Some(invoker) = &environment.invoker {
+                eprintln!("  invoker: {invoker}");
+            }
+            if !environment.options.is_empty() {
+                eprintln!("  options: {}", environment.options.join(" "));
+            }
+        }
+    }
+
+    // Output without a VM version doesn't have the options either
+    let (Some(old), Some(new)) = (old, new) else {
+        return;
+    };
+    let (Some(old_vm), Some(new_vm)) = (&old.vm, &new.vm) else {
+        return;
+    };
+    if old_vm != new_vm {
+        eprintln!(
+            "The files were run on different JVMs, {old_vm} and {new_vm}, which alone can change the scores"
+        );
+    }
+    if old.options != new.options {
+        let list = |options: &[String]| match options {
+            [] => "none".to_string(),
+            options => options.join(" "),
+        };
+        eprintln!(
+            "The files were run with different JVM options, {} and {}, which alone can change the scores",
+            list(&old.options),
+            list(&new.options)
+        );
+    }
 }
 
-fn parse_file<P: AsRef<Path>>(
-    path: P,
-) -> Result<(Vec<BenchResult>, Vec<ParseError>), std::io::Error> {
-    let file_contents = std::fs::read_to_string(path)?;
-    let blocks = file_contents.split("\n\n");
-    let last = blocks.last().unwrap_or_default();
-    Ok(parse_block(last))
+fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
 }
 
-fn calculate_delta(new_bench_result: &BenchResult, old_bench_result: &BenchResult) -> BenchDiff {
-    BenchDiff {
-        name: new_bench_result.name.clone(),
-        mode: new_bench_result.mode,
-        new_score: new_bench_result.score,
-        old_score: old_bench_result.score,
-        diff: (new_bench_result.score - old_bench_result.score) / old_bench_result.score,
-        units: new_bench_result.units.clone(),
+fn use_color(config: &Config) -> bool {
+    match config.output.color {
+        ColorChoice::Auto => config.output.file.is_none() && io::stdout().is_terminal(),
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
     }
 }
 
-fn compare_benchmark_results(
-    old_results: Vec<BenchResult>,
-    new_results: Vec<BenchResult>,
-) -> Vec<BenchDiff> {
-    old_results
+/// Colors significant improvements green and regressions red. The rows that aren't comparisons,
+/// like class headings, are `None`, and they're columns if the table is transposed.
+fn colorize_diffs(table: &mut Table, rows: &[Option<&BenchComparison>], transposed: bool) {
+    let Some(column) = BenchComparison::headers().iter().position(|h| h == "diff") else {
+        return;
+    };
+
+    for (i, row) in rows.iter().enumerate() {
+        let Some(diff) = row
+            .and_then(BenchComparison::as_changed)
+            .filter(|d| d.significant)
+        else {
+            continue;
+        };
+        let color = match diff.status() {
+            "better" => Color::FG_GREEN,
+            "worse" => Color::FG_RED,
+            _ => continue,
+        };
+        // The first row of the table is the header
+        let cell = if transposed {
+            Cell::new(column, i + 1)
+        } else {
+            Cell::new(i + 1, column)
+        };
+        table.with(Modify::new(cell).with(color));
+    }
+}
+
+/// Warns about files no benchmarks were parsed from, which usually means they aren't in a format
+/// that's understood, and fails the run with --strict.
+fn check_empty(config: &Config, files: &[(&String, &Vec<BenchResult>)]) -> Result<(), RunError> {
+    for (path, results) in files {
+        if !results.is_empty() {
+            continue;
+        }
+        if config.strict {
+            return Err(RunError::Empty {
+                path: path.to_string(),
+            });
+        }
+        eprintln!("Parsed 0 benchmarks from {path}, is the format correct?");
+    }
+    Ok(())
+}
+
+/// Prints the rows of each file that couldn't be parsed, which fails the run with --strict.
+fn check_parse_errors(config: &Config, diagnostics: &Diagnostics) -> Result<(), RunError> {
+    if diagnostics.is_empty() {
+        return Ok(());
+    }
+
+    if config.strict {
+        eprintln!("Some benchmark results couldn't be parsed:");
+    } else {
+        eprintln!("Some benchmark results couldn't be parsed, ignoring those rows and continuing:");
+    }
+    for line in diagnostics.to_string().lines() {
+        eprintln!("  {line}");
+    }
+
+    if config.strict {
+        Err(RunError::Parse {
+            count: diagnostics.count(),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Describes the diffs that regressed by more than their threshold. The thresholds are looked
+/// up by the full names, so this has to be done before they're shortened.
+fn find_regressions<'a>(
+    diffs: impl IntoIterator<Item = &'a BenchDiff>,
+    thresholds: &Thresholds,
+) -> Vec<String> {
+    diffs
         .into_iter()
-        .filter_map(|o| {
-            new_results
-                .iter()
-                .find(|n| n.name == o.name && n.units == o.units)
-                .and_then(|n| Some(calculate_delta(n, &o)))
+        .filter(|diff| thresholds.exceeded_by(diff))
+        .map(|diff| {
+            format!(
+                "{} regressed by {} (threshold {})",
+                diff.display_name(),
+                diff.change_str(),
+                thresholds.describe(diff)
+            )
+        })
+        .collect()
+}
+
+/// Describes the benchmarks that improved by less than required, including named ones that
+/// weren't compared at all. A name also matches benchmarks it's a dot-separated suffix of.
+/// Like regressions, this has to be done before names are shortened.
+fn find_insufficient_improvements(
+    diffs: &[&BenchDiff],
+    requirements: &[(Option<String>, f64)],
+) -> Vec<String> {
+    let mut failures = vec![];
+    for (name, required) in requirements {
+        let mut found = false;
+        for diff in diffs {
+            if name
+                .as_ref()
+                .is_some_and(|n| *n != diff.name && !diff.name.ends_with(&format!(".{n}")))
+            {
+                continue;
+            }
+            found = true;
+            if diff.improvement() * 100.0 < *required {
+                failures.push(format!(
+                    "{} changed by {} (required an improvement of {required}%)",
+                    diff.display_name(),
+                    diff.diff_str()
+                ));
+            }
+        }
+        if let (Some(name), false) = (name, found) {
+            failures.push(format!(
+                "{name} wasn't compared (required an improvement of {required}%)"
+            ));
+        }
+    }
+    failures
+}
+
+/// Describes the diffs that regressed by more than --warn-threshold, but not by more than their
+/// threshold for failing.
+fn find_warnings<'a>(
+    diffs: impl IntoIterator<Item = &'a BenchDiff>,
+    config: &Config,
+) -> Vec<String> {
+    let Some(warn) = config.thresholds.warn else {
+        return vec![];
+    };
+    diffs
+        .into_iter()
+        .filter(|diff| {
+            !diff.noise && diff.exceeds_threshold(warn) && !config.thresholds.fail.exceeded_by(diff)
+        })
+        .map(|diff| {
+            format!(
+                "{} regressed by {} (warn threshold {warn}%)",
+                diff.display_name(),
+                diff.change_str()
+            )
         })
         .collect()
 }
 
-fn run(config: &Config) {
-    let (new_results, new_errors) = match parse_file(&config.new_file) {
-        Ok(r) => r,
-        Err(e) => {
-            eprintln!("Problem parsing new benchmarks file: {e}");
-            process::exit(1);
+/// Prints the regressions and warnings, and with --warn-threshold the severity of the worst of
+/// them, which only fails the run if some benchmarks regressed by more than their threshold.
+fn report_outcome(
+    config: &Config,
+    regressions: Vec<String>,
+    improvements: Vec<String>,
+    warnings: Vec<String>,
+) -> Result<(), RunError> {
+    for warning in &warnings {
+        eprintln!("{warning}");
+    }
+    let result =
+        report_regressions(regressions).and(report_insufficient_improvements(improvements));
+
+    if config.thresholds.warn.is_some() {
+        let severity = match result {
+            Err(_) => "FAIL",
+            Ok(()) if !warnings.is_empty() => "WARN",
+            Ok(()) => "OK",
+        };
+        eprintln!("severity: {severity}");
+    }
+    result
+}
+
+fn report_regressions(regressions: Vec<String>) -> Result<(), RunError> {
+    for regression in &regressions {
+        eprintln!("{regression}");
+    }
+
+    if regressions.is_empty() {
+        Ok(())
+    } else {
+        Err(RunError::Regression {
+            count: regressions.len(),
+        })
+    }
+}
+
+fn report_insufficient_improvements(failures: Vec<String>) -> Result<(), RunError> {
+    for failure in &failures {
+        eprintln!("{failure}");
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(RunError::InsufficientImprovement {
+            count: failures.len(),
+        })
+    }
+}
+
+fn write_trends(
+    out: &mut impl Write,
+    config: &Config,
+    trends: &[BenchTrend],
+    labels: &[String],
+) -> io::Result<()> {
+    match config.output.format {
+        OutputFormat::Table | OutputFormat::Github => {
+            if let OutputFormat::Github = config.output.format {
+                let diffs = config.trend_diffs(trends);
+                for annotation in github_annotations(&diffs, &config.thresholds.fail) {
+                    writeln!(out, "{annotation}")?;
+                }
+            }
+
+            let mut rows = trend_rows(trends, labels, &config.output.number_format);
+            rows[0].push("trend".to_string());
+            for (row, trend) in rows[1..].iter_mut().zip(trends) {
+                row.push(trend.sparkline());
+            }
+
+            let mut table = Builder::from(rows).build();
+            config.output.style.apply(&mut table);
+            writeln!(out, "{}", table)?;
+        }
+        OutputFormat::Markdown => writeln!(
+            out,
+            "{}",
+            rows_to_markdown(&trend_rows(trends, labels, &config.output.number_format))
+        )?,
+        OutputFormat::Json => {
+            let json: Vec<Value> = trends.iter().map(BenchTrend::to_json).collect();
+            config.write_json(out, json)?;
+        }
+        OutputFormat::Csv => {
+            writeln!(out, "{}", BenchTrend::csv_header(labels))?;
+            for trend in trends {
+                writeln!(out, "{}", trend.to_csv())?;
+            }
+        }
+        OutputFormat::Junit => {
+            let result: Vec<BenchComparison> = config
+                .trend_diffs(trends)
+                .into_iter()
+                .map(BenchComparison::Changed)
+                .collect();
+            writeln!(out, "{}", to_junit(&result, &config.thresholds.fail))?;
+        }
+        OutputFormat::Line => {
+            for trend in trends {
+                let line = trend.to_line(&config.output.number_format, config.invert);
+                writeln!(out, "{line}")?;
+            }
+        }
+    }
+    out.flush()
+}
+
+fn write_comparisons(
+    out: &mut impl Write,
+    config: &Config,
+    result: &[BenchComparison],
+) -> io::Result<()> {
+    let summary =
+        || match Summary::from_diffs(result.iter().filter_map(BenchComparison::as_changed)) {
+            Some(summary) => summary.to_string(),
+            None => "no comparable benchmarks found".to_string(),
+        };
+
+    if config.output.only_regressions && result.is_empty() && config.is_human_readable() {
+        writeln!(out, "no regressions")?;
+        return out.flush();
+    }
+
+    // The formats for other tools always get every row, so none are silently lost
+    let (shown, hidden) = match config.output.max_rows {
+        Some(max) if config.is_human_readable() && result.len() > max => {
+            (&result[..max], result.len() - max)
+        }
+        _ => (result, 0),
+    };
+    let more = format!("…and {hidden} more benchmarks");
+
+    match config.output.format {
+        OutputFormat::Table | OutputFormat::Github => {
+            if let OutputFormat::Github = config.output.format {
+                let diffs = result.iter().filter_map(BenchComparison::as_changed);
+                for annotation in github_annotations(diffs, &config.thresholds.fail) {
+                    writeln!(out, "{annotation}")?;
+                }
+            }
+
+            let show_p99 = has_percentiles(shown);
+            let mut headers = config.headers();
+            if show_p99 {
+                headers.push(P99_DIFF_HEADER.into());
+            }
+
+            // Without grouping, all the rows are in one class without a heading. The headings are
+            // worked out over all of a class, including the rows past --max-rows
+            let classes: Vec<&[BenchComparison]> = if config.output.group_by_class {
+                result.chunk_by(|a, b| a.class() == b.class()).collect()
+            } else {
+                vec![shown]
+            };
+
+            let columns = headers.len();
+            let mut records = vec![headers];
+            let mut rows = vec![];
+            let mut remaining = shown.len();
+            for class in classes {
+                if remaining == 0 {
+                    break;
+                }
+                let visible = &class[..class.len().min(remaining)];
+                remaining -= visible.len();
+
+                if let (true, Some(first)) = (config.output.group_by_class, class.first()) {
+                    let mut cells = vec![Cow::Borrowed(""); columns];
+                    cells[0] = Cow::Owned(class_heading(first.class(), class));
+                    records.push(cells);
+                    rows.push(None);
+                }
+                for comparison in visible {
+                    let mut cells = comparison.cells(&config.output.number_format);
+                    if config.output.show_counts {
+                        cells.extend(comparison.count_cells());
+                    }
+                    if config.output.show_threads {
+                        cells.extend(comparison.thread_cells());
+                    }
+                    if show_p99 {
+                        cells.push(comparison.p99_cell());
+                    }
+                    records.push(cells);
+                    rows.push(Some(comparison));
+                }
+            }
+
+            let mut builder = Builder::default();
+            if config.output.transpose {
+                // Each benchmark gets a column, with the first one naming the rows
+                for i in 0..columns {
+                    builder.push_record(records.iter().map(|record| record[i].clone()));
+                }
+            } else {
+                for record in records {
+                    builder.push_record(record);
+                }
+            }
+
+            let mut table = builder.build();
+            config.output.style.apply(&mut table);
+
+            if use_color(config) {
+                colorize_diffs(&mut table, &rows, config.output.transpose);
+            }
+
+            writeln!(out, "{}", table)?;
+            if hidden > 0 {
+                writeln!(out, "{more}")?;
+            }
+            writeln!(out)?;
+            writeln!(out, "{}", summary())?;
+        }
+        OutputFormat::Markdown => {
+            writeln!(
+                out,
+                "{}",
+                to_markdown(
+                    shown,
+                    config.headers(),
+                    config.output.show_counts,
+                    config.output.show_threads,
+                    &config.output.number_format
+                )
+            )?;
+            if hidden > 0 {
+                writeln!(out, "\n{more}")?;
+            }
+            writeln!(out)?;
+            writeln!(out, "{}", summary())?;
+        }
+        OutputFormat::Json => {
+            let json: Vec<Value> = result.iter().map(BenchComparison::to_json).collect();
+            config.write_json(out, json)?;
+        }
+        OutputFormat::Csv => {
+            writeln!(out, "{}", BenchDiff::CSV_HEADER)?;
+            for comparison in result {
+                writeln!(out, "{}", comparison.to_csv())?;
+            }
+        }
+        OutputFormat::Junit => writeln!(out, "{}", to_junit(result, &config.thresholds.fail))?,
+        OutputFormat::Line => {
+            for comparison in shown {
+                writeln!(out, "{}", comparison.to_line(&config.output.number_format))?;
+            }
+            if hidden > 0 {
+                writeln!(out, "{more}")?;
+            }
+            writeln!(out, "{}", summary())?;
+        }
+    }
+    out.flush()
+}
+
+/// Shows the results of a file on their own, like those of the new file when there's no old
+/// one to compare to.
+fn run_baseline(
+    config: &Config,
+    path: &String,
+    mut results: Vec<BenchResult>,
+    errors: Vec<ParseError>,
+    out: &mut impl Write,
+) -> Result<(), RunError> {
+    let mut diagnostics = Diagnostics::default();
+    diagnostics.add(path, errors);
+    check_parse_errors(config, &diagnostics)?;
+    check_empty(config, &[(path, &results)])?;
+
+    results.retain(|r| config.filters.is_selected(r));
+
+    if config.output.sort == SortOrder::Name {
+        results.sort_by_cached_key(|r| (r.display_name(), r.mode));
+    }
+
+    if config.shorten_names() {
+        shorten_names(results.iter_mut().map(|r| &mut r.name));
+    }
+
+    write_results(out, config, results).map_err(RunError::Output)
+}
+
+fn write_results(
+    out: &mut impl Write,
+    config: &Config,
+    results: Vec<BenchResult>,
+) -> io::Result<()> {
+    match config.output.format {
+        OutputFormat::Table | OutputFormat::Github => {
+            let mut table = Table::new(results);
+            config.output.style.apply(&mut table);
+            writeln!(out, "{}", table)?;
+            out.flush()
+        }
+        // Every benchmark is new, which the other formats can already show
+        _ => {
+            let result: Vec<BenchComparison> =
+                results.into_iter().map(BenchComparison::Added).collect();
+            write_comparisons(out, config, &result)
+        }
+    }
+}
+
+/// Shows the results of each file without comparing them, to check how they were parsed.
+fn run_list(config: &Config, out: &mut impl Write) -> Result<(), RunError> {
+    let mut paths = vec![];
+    // The old file is left out when only one is given
+    if !config.old_file.is_empty() {
+        paths.push(&config.old_file);
+    }
+    paths.extend(&config.middle_files);
+    paths.push(&config.new_file);
+
+    for (i, (path, parsed)) in paths.iter().zip(parse_inputs(config, &paths)).enumerate() {
+        let (results, errors) = parsed.map_err(|error| RunError::File {
+            path: path.to_string(),
+            error,
+        })?;
+        if paths.len() > 1 {
+            let separator = if i > 0 { "\n" } else { "" };
+            writeln!(out, "{separator}{path}:").map_err(RunError::Output)?;
         }
+        run_baseline(config, path, results, errors, out)?;
+    }
+    Ok(())
+}
+
+/// Prints how long a step took, if it was timed.
+fn report_time(step: &str, start: Option<Instant>) {
+    if let Some(start) = start {
+        eprintln!("{step} took {:.2?}", start.elapsed());
+    }
+}
+
+/// Prints the results of the new file as the table JMH prints, to compare with the input.
+fn run_round_trip(config: &Config, out: &mut impl Write) -> Result<(), RunError> {
+    let parsed = parse_inputs(config, &[&config.new_file]).into_iter().next();
+    let (results, errors) = parsed
+        .expect("the file should have been parsed")
+        .map_err(RunError::NewFile)?;
+
+    let mut diagnostics = Diagnostics::default();
+    diagnostics.add(&config.new_file, errors);
+    check_parse_errors(config, &diagnostics)?;
+
+    writeln!(out, "{}", to_jmh_table(&results))
+        .and_then(|_| out.flush())
+        .map_err(RunError::Output)
+}
+
+/// Parses the files at the same time, each on a thread of its own, returning the results in
+/// the same order as the paths.
+fn parse_inputs(
+    config: &Config,
+    paths: &[&String],
+) -> Vec<io::Result<(Vec<BenchResult>, Vec<ParseError>)>> {
+    let mut parsed: Vec<_> = thread::scope(|scope| {
+        let handles: Vec<_> = paths
+            .iter()
+            .map(|path| {
+                scope.spawn(move || {
+                    let start = config.start_timer();
+                    let json = config.baseline_json && **path == config.old_file;
+                    let parsed = parse_input(path, config.decimal_separator, json);
+                    report_time(&format!("Parsing {path}"), start);
+                    parsed
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+            .collect()
+    });
+
+    for (path, parsed) in paths.iter().zip(&mut parsed) {
+        let Ok((results, _)) = parsed else {
+            continue;
+        };
+
+        // The warmup is compared to the score JMH reported, before it's replaced
+        if let Some(max_gap) = config.warmup_check {
+            for warning in warmup_warnings(path, results, max_gap) {
+                eprintln!("{warning}");
+            }
+        }
+
+        if config.statistic == Statistic::Mean {
+            continue;
+        }
+        let missing = use_statistic(results, config.statistic);
+        if missing > 0 {
+            eprintln!(
+                "{path}: {missing} benchmark(s) have no raw data, using the reported mean instead of the {}",
+                config.statistic
+            );
+        }
+    }
+    parsed
+}
+
+/// Describes the benchmarks whose last warmup iterations scored more than `max_gap` percent
+/// away from their score, which hints that they weren't warmed up. Only the log of text output
+/// has warmup scores, so a file without any, like a JSON report, gets a warning that it can't
+/// be checked instead.
+fn warmup_warnings(path: &str, results: &[BenchResult], max_gap: f64) -> Vec<String> {
+    let mut warnings = vec![];
+    let mut missing = 0;
+    for result in results {
+        let Some(gap) = result.warmup_gap() else {
+            missing += 1;
+            continue;
+        };
+        if gap.abs() * 100.0 > max_gap {
+            warnings.push(format!(
+                "{path}: the last warmup iterations of {} are {:+.2}% off its score, so it may not have been warmed up",
+                result.display_name(),
+                gap * 100.0
+            ));
+        }
+    }
+    if missing > 0 && missing == results.len() {
+        warnings.push(format!(
+            "{path}: --warmup-check can't check any benchmarks, since warmup scores are only in the log of text output, not in JSON or CSV reports"
+        ));
+    } else if missing > 0 {
+        warnings.push(format!(
+            "{path}: {missing} benchmark(s) have no warmup scores, which are only in the log of text output"
+        ));
+    }
+    warnings
+}
+
+/// Shows the scores of each benchmark across all the files, instead of a diff of two.
+fn run_trend(config: &Config, out: &mut impl Write) -> Result<(), RunError> {
+    let mut paths = vec![&config.old_file];
+    paths.extend(&config.middle_files);
+    paths.push(&config.new_file);
+
+    let mut runs = vec![];
+    let mut diagnostics = Diagnostics::default();
+    for (i, (path, parsed)) in paths.iter().zip(parse_inputs(config, &paths)).enumerate() {
+        let (results, e) = parsed.map_err(|error| match i {
+            0 => RunError::OldFile(error),
+            i if i == paths.len() - 1 => RunError::NewFile(error),
+            _ => RunError::File {
+                path: path.to_string(),
+                error,
+            },
+        })?;
+        runs.push(results);
+        diagnostics.add(*path, e);
+    }
+
+    check_parse_errors(config, &diagnostics)?;
+    let files: Vec<(&String, &Vec<BenchResult>)> = paths.iter().copied().zip(runs.iter()).collect();
+    check_empty(config, &files)?;
+
+    let start = config.start_timer();
+    if let Some((_, old_runs)) = runs.split_last_mut() {
+        for results in old_runs {
+            rename_prefixes(results, &config.renames);
+        }
+    }
+
+    for results in &mut runs {
+        results.retain(|r| config.filters.is_selected(r));
+    }
+
+    let mut trends = compare_trend(runs);
+
+    if config.output.hide_missing {
+        trends.retain(|t| t.runs.iter().all(Option::is_some));
+    }
+
+    sort_trends(&mut trends, config.output.sort);
+
+    let diffs = config.trend_diffs(&trends);
+    let regressions = find_regressions(&diffs, &config.thresholds.fail);
+    let warnings = find_warnings(&diffs, config);
+    let improvements = find_insufficient_improvements(
+        &diffs.iter().collect::<Vec<_>>(),
+        &config.thresholds.required_improvements,
+    );
+    report_time("Comparing", start);
+
+    if config.shorten_names() {
+        shorten_names(trends.iter_mut().map(|t| &mut t.name));
+    }
+
+    let labels: Vec<String> = paths.iter().map(|p| p.to_string()).collect();
+    write_trends(out, config, &trends, &labels).map_err(RunError::Output)?;
+
+    report_outcome(config, regressions, improvements, warnings)
+}
+
+/// The config file read from the working directory when --config isn't given.
+const CONFIG_FILE: &str = "jmhcmp.toml";
+
+/// Reads the per-benchmark thresholds from the config file, if there is one.
+fn load_thresholds(config: &mut Config) -> Result<(), RunError> {
+    let path = match &config.config_file {
+        Some(path) => path.clone(),
+        None if Path::new(CONFIG_FILE).exists() => CONFIG_FILE.to_string(),
+        None => return Ok(()),
     };
+    let input = fs::read_to_string(&path).map_err(|e| RunError::Config {
+        path: path.clone(),
+        error: e.to_string(),
+    })?;
+    config
+        .thresholds
+        .fail
+        .parse_toml(&input)
+        .map_err(|error| RunError::Config { path, error })
+}
 
-    let (old_results, old_errors) = match parse_file(&config.old_file) {
-        Ok(r) => r,
-        Err(e) => {
-            eprintln!("Problem parsing old benchmarks file: {e}");
-            process::exit(1);
+fn run(config: &Config) -> Result<(), RunError> {
+    match &config.output.file {
+        Some(path) => {
+            let file = File::create(path).map_err(RunError::Output)?;
+            run_with_output(config, &mut BufWriter::new(file))
         }
+        None => run_with_output(config, &mut io::stdout().lock()),
+    }
+}
+
+fn run_with_output(config: &Config, out: &mut impl Write) -> Result<(), RunError> {
+    if config.list {
+        return run_list(config, out);
+    }
+
+    if config.round_trip {
+        return run_round_trip(config, out);
+    }
+
+    if !config.middle_files.is_empty() {
+        return run_trend(config, out);
+    }
+
+    let paths = if config.baseline {
+        vec![&config.new_file]
+    } else {
+        vec![&config.new_file, &config.old_file]
     };
+    let mut parsed = parse_inputs(config, &paths).into_iter();
+
+    let (mut new_results, new_errors) = parsed
+        .next()
+        .expect("the new file should have been parsed")
+        .map_err(RunError::NewFile)?;
+    if config.baseline {
+        return run_baseline(config, &config.new_file, new_results, new_errors, out);
+    }
+
+    let (mut old_results, old_errors) =
+        match parsed.next().expect("the old file should have been parsed") {
+            Ok(old) => old,
+            // There's nothing to compare against on the first run
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                eprintln!(
+                    "{} doesn't exist, so only showing the new results",
+                    config.old_file
+                );
+                return run_baseline(config, &config.new_file, new_results, new_errors, out);
+            }
+            Err(e) => return Err(RunError::OldFile(e)),
+        };
+
+    let mut diagnostics = Diagnostics::default();
+    diagnostics.add(&config.old_file, old_errors);
+    diagnostics.add(&config.new_file, new_errors);
+    check_parse_errors(config, &diagnostics)?;
+    check_empty(
+        config,
+        &[
+            (&config.old_file, &old_results),
+            (&config.new_file, &new_results),
+        ],
+    )?;
+
+    let start = config.start_timer();
+    rename_prefixes(&mut old_results, &config.renames);
+
+    old_results.retain(|r| config.filters.is_selected(r));
+    new_results.retain(|r| config.filters.is_selected(r));
 
-    if old_errors.len() > 0 || new_errors.len() > 0 {
-        println!("There were come errors found while parsing the benchmark results, ignoring those rows and continuing");
+    let (old_environment, new_environment) = read_environments(config);
+    warn_if_swapped(config, old_environment.as_ref(), new_environment.as_ref());
+    check_environments(config, old_environment.as_ref(), new_environment.as_ref());
+
+    if config.ignore_case {
+        match_names_ignoring_case(&mut old_results, &new_results);
+    }
+
+    if config.allow_reciprocal {
+        match_reciprocals(&mut old_results, &new_results);
     }
 
-    let result = compare_benchmark_results(old_results, new_results);
+    warn_if_modes_differ(config, &old_results, &new_results);
 
-    let mut table = Table::new(result);
-    table.with(Style::blank());
+    for change in find_mode_changes(&old_results, &new_results) {
+        eprintln!(
+            "{} changed mode from {} to {}, so it can't be compared",
+            change.name, change.old_mode, change.new_mode
+        );
+    }
 
-    println!("{}", table);
+    let secondary = config.secondary.as_ref().map(|metric| {
+        let mut result = compare_benchmark_results_by(
+            secondary_results(&old_results, metric),
+            secondary_results(&new_results, metric),
+            &config.match_key,
+        );
+        refine_comparisons(config, &mut result);
+        // Name the rows the way JMH does in text output, to tell them apart from the scores
+        for comparison in &mut result {
+            comparison.name_mut().push_str(&format!(":·{metric}"));
+        }
+        (metric, result)
+    });
+
+    // The rows of the metric in text output are in the secondary table, not the primary one
+    if let Some(metric) = &config.secondary {
+        old_results.retain(|r| !is_secondary_row(r, metric));
+        new_results.retain(|r| !is_secondary_row(r, metric));
+    }
+
+    let mut result = compare_benchmark_results_by(old_results, new_results, &config.match_key);
+    refine_comparisons(config, &mut result);
+    warn_if_threads_differ(&result);
+
+    // The formats for other tools can only hold one list, so the secondary metric is added to it
+    let mut secondary = match secondary {
+        Some((_, mut secondary)) if !config.is_human_readable() => {
+            result.append(&mut secondary);
+            None
+        }
+        secondary => secondary,
+    };
+
+    let regressions = find_regressions(
+        result
+            .iter()
+            .chain(secondary.iter().flat_map(|(_, s)| s))
+            .filter_map(BenchComparison::as_changed),
+        &config.thresholds.fail,
+    );
+    let warnings = find_warnings(
+        result
+            .iter()
+            .chain(secondary.iter().flat_map(|(_, s)| s))
+            .filter_map(BenchComparison::as_changed),
+        config,
+    );
+    let diffs: Vec<&BenchDiff> = result
+        .iter()
+        .filter_map(BenchComparison::as_changed)
+        .collect();
+    let improvements =
+        find_insufficient_improvements(&diffs, &config.thresholds.required_improvements);
+    report_time("Comparing", start);
+
+    // Added and removed benchmarks are changes too
+    let comparisons = || result.iter().chain(secondary.iter().flat_map(|(_, s)| s));
+    let unchanged = comparisons().all(|c| {
+        c.as_changed()
+            .is_some_and(|d| !config.thresholds.fail.changed_by(d))
+    });
+
+    if config.output.quiet && config.is_human_readable() && unchanged {
+        writeln!(
+            out,
+            "{} benchmarks compared, no significant changes",
+            comparisons().count()
+        )
+        .map_err(RunError::Output)?;
+    } else {
+        if config.shorten_names() {
+            shorten_names(result.iter_mut().map(BenchComparison::name_mut));
+            if let Some((_, secondary)) = &mut secondary {
+                shorten_names(secondary.iter_mut().map(BenchComparison::name_mut));
+            }
+        }
+
+        write_comparisons(out, config, &result).map_err(RunError::Output)?;
+        if let Some((metric, secondary)) = &secondary {
+            writeln!(out)
+                .and_then(|_| writeln!(out, "{metric}:"))
+                .and_then(|_| write_comparisons(out, config, secondary))
+                .map_err(RunError::Output)?;
+        }
+    }
+
+    // Any diffs between the same file, or two runs of the same code, are noise
+    if config.self_variance || config.old_file == config.new_file {
+        report_self_variance(&result);
+    }
+
+    report_outcome(config, regressions, improvements, warnings)
+}
+
+/// Prints the largest diff between the files, which is the run-to-run variance when they were
+/// measured from the same code, to help pick a --threshold.
+fn report_self_variance(result: &[BenchComparison]) {
+    let largest = result
+        .iter()
+        .filter_map(BenchComparison::as_changed)
+        .max_by(|a, b| a.diff.abs().total_cmp(&b.diff.abs()));
+    if let Some(diff) = largest {
+        eprintln!(
+            "The largest run-to-run diff is {} for {}, so thresholds below {:.5}% are within the noise",
+            diff.diff_str(),
+            diff.display_name(),
+            diff.diff.abs() * 100.0
+        );
+    }
+}
+
+/// Applies the options that decide which comparisons are shown, and how.
+fn refine_comparisons(config: &Config, result: &mut Vec<BenchComparison>) {
+    if config.invert {
+        invert_improvements(result);
+    }
+
+    if let Some(noise) = config.thresholds.noise {
+        mark_noise(result, noise);
+    }
+
+    mark_high_variance(result, config.thresholds.max_rsd);
+
+    if config.output.hide_missing {
+        result.retain(|c| !c.is_missing());
+    }
+
+    if config.output.only_regressions {
+        // Changes within the threshold are treated as noise
+        result.retain(|c| {
+            c.as_changed()
+                .is_some_and(|d| config.thresholds.fail.exceeded_by(d))
+        });
+    }
+
+    sort_comparisons(result, config.output.sort);
+    if config.output.group_by_class {
+        group_by_class(result);
+    }
+}
+
+fn usage(program: &str) -> String {
+    format!(
+        "\
+Compare the results of two JMH benchmark runs.
+
+Usage: {program} [OPTIONS] <old_file> [<file>...] <new_file>
+
+When more than two files are given, the score of each benchmark in every file is shown
+along with the diff between the first and last. One of the files can be - to read it
+from stdin, and any of them can be an http:// or https:// URL to download.
+
+Options, which can also be given as --option=value:
+  --threshold <percent>  Exit with status 1 if a benchmark regressed by more than this,
+                         instead of the defaults of 3 for thrpt, 2 for avgt and sample,
+                         and 1 for ss
+  --fail-threshold <percent>
+                         The same as --threshold
+  --warn-threshold <percent>
+                         Print a warning, but exit with status 0, if a benchmark regressed
+                         by more than this but not by more than the threshold, and the
+                         severity of the worst regression: OK, WARN or FAIL
+  --abs-threshold <value>
+                         Only count a benchmark as regressed if its score also got worse
+                         by more than this in its units, for benchmarks close to zero
+  --threshold-logic <logic>
+                         How --abs-threshold combines with the percentage: and (default),
+                         so a regression has to exceed both, or or, so either is enough
+  --fail-under-improvement [<name>=]<percent>
+                         Exit with status 1 if the named benchmark, or any if no name
+                         is given, improved by less than this. Can be given more than
+                         once
+  --config <file>        Read per-benchmark thresholds from the file instead of
+                         jmhcmp.toml
+  --format <format>      Output format: table (default), json, csv, markdown,
+                         junit, github or line
+  --json-pretty          Indent the JSON output instead of writing it on one line
+  --max-rows <count>     Only show the first rows of the table, after sorting. The formats
+                         for other tools still get every benchmark
+  --quiet                Only print a line saying how many benchmarks were compared if
+                         none of them changed by more than the threshold
+  --transpose            Show the benchmarks as the columns of the table instead of its
+                         rows, which reads better when there are only a few
+  --group-by-class       Keep the benchmarks of each class together in the table, under a
+                         heading with their geomean improvement
+  --self-variance        Print the largest diff as the noise floor, for two runs of the
+                         same code. Done anyway when both paths are the same
+  --timing               Print how long parsing each file and comparing them took
+  --sort <order>         Row order: none (default), name or diff
+  --decimal-comma        Read scores like 1.234,5 from text and CSV files written in a
+                         locale with a decimal comma
+  --metric <statistic>   Compare this statistic of the iteration scores in JSON files:
+                         mean (default, the score JMH reports), median, min or max
+  --filter <regex>       Only compare benchmarks whose name matches the regex. When given
+                         more than once, a benchmark only has to match one of them
+  --rename <old>=<new>   Replace the old prefix of benchmark names in the old file with
+                         the new one, so renamed benchmarks are still compared. Can be
+                         given more than once
+  --include-file <file>  Only compare the benchmarks named in the file, one per line. An
+                         empty file matches no benchmarks
+  --exclude-file <file>  Don't compare the benchmarks named in the file, one per line. An
+                         empty file leaves them all in
+  --ignore-case          Match benchmark names that only differ in case
+  --allow-reciprocal     Compare benchmarks measured as throughput in one file and as
+                         average time in the other
+  --invert               Count lower throughput and higher times as improvements, for
+                         harnesses that don't report what the mode says
+  --short-names          Leave out the package all the benchmark names have in common
+  --hide-missing         Don't show benchmarks missing from one of the files
+  --show-counts          Show the number of measurement iterations in each file
+  --show-threads         Show the number of threads of each file, when known
+  --show-env             Print the JVM each file was run on
+  --key-field <field>    Match benchmarks on their name (default), a param or the id param
+  --key-param <param>    The parameter to match on with --key-field param
+  --only-regressions     Only show benchmarks that regressed by more than the threshold
+  --color <when>         Color the diffs: auto (default), always or never
+  --old-label <label>    Name the old score column after the label, like a branch name
+  --new-label <label>    Name the new score column after the label
+  --secondary <metric>   Also compare a secondary metric, like gc.alloc.rate.norm
+  --style <style>        Table style: blank (default), ascii, modern, rounded, sharp,
+                         psql or markdown
+  --output <file>        Write the report to a file instead of stdout
+  --noise <percent>      Show diffs smaller than this as ~0% and count them as unchanged
+  --warmup-check <percent>
+                         Warn about benchmarks whose last warmup iterations scored more
+                         than this away from the score, from the log of text output
+  --max-rsd <percent>    Mark diffs as high variance if the error of a score is more
+                         than this percentage of it, 20 by default
+  --precision <places>   Show scores with this many decimal places instead of three
+                         significant figures
+  --diff-style <style>   What the diff column shows: ratio (default), absolute or both
+  --si                   Show large scores with a k, M or G suffix
+  --baseline             Only show the results of the new file, which is also done
+                         when the old file doesn't exist
+  --baseline-json <file> Compare against this file instead of an old_file argument,
+                         always parsing it as JMH JSON output
+  --list                 Only show the results parsed from each file, without comparing
+                         them
+  --strict               Fail if any row of the files can't be parsed
+  -h, --help             Print this help and exit
+
+Exit status:
+  0  No benchmark regressed by more than the threshold
+  1  Some benchmarks regressed by more than the threshold, or didn't improve by as
+     much as --fail-under-improvement requires
+  2  The arguments were invalid
+  3  A file couldn't be read, or the report couldn't be written
+  4  Some rows couldn't be parsed, or a file had no benchmarks, with --strict"
+    )
 }
 
 fn main() {
-    let config = Config::build(env::args()).unwrap_or_else(|e| {
+    let args: Vec<String> = env::args().collect();
+
+    if args.iter().skip(1).any(|a| a == "-h" || a == "--help") {
+        let program = args.first().map_or("jmhcmp", String::as_str);
+        println!("{}", usage(program));
+        process::exit(0);
+    }
+
+    let mut config = Config::build(args.into_iter()).unwrap_or_else(|e| {
         eprintln!("Problem parsing arguments: {e}");
-        process::exit(1);
+        eprintln!("Run with --help to see the usage");
+        process::exit(ARGUMENT_ERROR_CODE);
     });
-    run(&config);
+
+    if let Err(e) = load_thresholds(&mut config).and_then(|_| run(&config)) {
+        eprintln!("{e}");
+        process::exit(e.exit_code());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jmhcmp::calculate_delta;
+    use serde_json::json;
+
+    fn diff(old: &str, new: &str) -> BenchDiff {
+        let old: BenchResult = old.parse().unwrap();
+        let new: BenchResult = new.parse().unwrap();
+        calculate_delta(&new, &old)
+    }
+
+    fn config(args: &[&str]) -> Config {
+        let args = ["jmhcmp"].iter().chain(args).map(|a| a.to_string());
+        Config::build(args).unwrap()
+    }
+
+    fn config_error(args: &[&str]) -> String {
+        let args = ["jmhcmp"].iter().chain(args).map(|a| a.to_string());
+        Config::build(args).err().unwrap()
+    }
+
+    #[test]
+    fn options_take_values() {
+        let config = config(&["--threshold", "5", "--format", "json", "old.txt", "new.txt"]);
+        assert_eq!(config.thresholds.fail.default, Some(5.0));
+        assert!(matches!(config.output.format, OutputFormat::Json));
+        assert_eq!(config.old_file, "old.txt");
+        assert_eq!(config.new_file, "new.txt");
+    }
+
+    #[test]
+    fn invalid_choices_list_the_valid_ones() {
+        assert_eq!(
+            config_error(&["--sort", "size", "old.txt", "new.txt"]),
+            "Invalid value for --sort, expected none, name or diff"
+        );
+    }
+
+    #[test]
+    fn options_need_a_value() {
+        assert_eq!(
+            config_error(&["old.txt", "new.txt", "--threshold"]),
+            "Didn't get a value for --threshold"
+        );
+        assert_eq!(
+            config_error(&["--threshold", "x", "old.txt", "new.txt"]),
+            "Invalid value for --threshold"
+        );
+    }
+
+    #[test]
+    fn unknown_options_are_rejected() {
+        assert_eq!(
+            config_error(&["--thresold", "5", "old.txt", "new.txt"]),
+            "Unknown option --thresold"
+        );
+        assert_eq!(config(&["-", "new.txt"]).old_file, STDIN_PATH);
+    }
+
+    #[test]
+    fn insufficient_improvement_fails() {
+        // 10 ns/op to 9.8 ns/op is a 2% improvement
+        let small = diff("A.run avgt 5 10.0 ns/op", "A.run avgt 5 9.8 ns/op");
+        let large = diff("B.run avgt 5 10.0 ns/op", "B.run avgt 5 5.0 ns/op");
+        let diffs = [&small, &large];
+
+        let all = [(None, 5.0)];
+        let failures = find_insufficient_improvements(&diffs, &all);
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].starts_with("A.run changed by"), "{failures:?}");
+
+        let named = [
+            (Some("B.run".to_string()), 5.0),
+            (Some("run".to_string()), 1.0),
+        ];
+        assert!(find_insufficient_improvements(&diffs, &named).is_empty());
+
+        let missing = [(Some("C.run".to_string()), 5.0)];
+        assert_eq!(
+            find_insufficient_improvements(&diffs, &missing),
+            ["C.run wasn't compared (required an improvement of 5%)"]
+        );
+    }
+
+    #[test]
+    fn json_pretty() {
+        let json = || vec![json!({"name": "A.run", "diff": 0.5})];
+
+        let mut out = vec![];
+        let compact = config(&["old.json", "new.json"]);
+        compact.write_json(&mut out, json()).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "[{\"name\":\"A.run\",\"diff\":0.5}]\n"
+        );
+
+        let mut out = vec![];
+        let pretty = config(&["--json-pretty", "old.json", "new.json"]);
+        pretty.write_json(&mut out, json()).unwrap();
+        let golden = "\
+[
+  {
+    \"name\": \"A.run\",
+    \"diff\": 0.5
+  }
+]
+";
+        assert_eq!(String::from_utf8(out).unwrap(), golden);
+    }
+
+    #[test]
+    fn options_take_separate_or_joined_values() {
+        for args in [
+            [
+                "--threshold",
+                "5",
+                "--format",
+                "json",
+                "--old-label",
+                "main",
+            ]
+            .as_slice(),
+            ["--threshold=5", "--format=json", "--old-label=main"].as_slice(),
+        ] {
+            let args: Vec<&str> = args
+                .iter()
+                .chain(&["old.txt", "new.txt"])
+                .copied()
+                .collect();
+            let config = config(&args);
+            assert_eq!(config.thresholds.fail.default, Some(5.0));
+            assert!(matches!(config.output.format, OutputFormat::Json));
+            assert_eq!(config.output.old_label, "main");
+            assert_eq!(config.old_file, "old.txt");
+            assert_eq!(config.new_file, "new.txt");
+        }
+    }
+
+    #[test]
+    fn joined_values_can_contain_equals_signs() {
+        let config = config(&["--rename=com.old.=com.new.", "old.txt", "new.txt"]);
+        assert_eq!(
+            config.renames,
+            [("com.old.".to_string(), "com.new.".to_string())]
+        );
+    }
+
+    #[test]
+    fn flags_reject_joined_values() {
+        let args = ["jmhcmp", "--strict=yes", "old.txt", "new.txt"].map(str::to_string);
+        assert_eq!(
+            Config::build(args.into_iter()).unwrap_err(),
+            "--strict doesn't take a value"
+        );
+    }
+
+    #[test]
+    fn severity_bands() {
+        let config = config(&[
+            "--warn-threshold",
+            "2",
+            "--fail-threshold",
+            "5",
+            "old.txt",
+            "new.txt",
+        ]);
+        let outcome = |new_score: &str| {
+            let diff = diff(
+                "A.run avgt 5 100.0 ns/op",
+                &format!("A.run avgt 5 {new_score} ns/op"),
+            );
+            let regressions = find_regressions([&diff], &config.thresholds.fail);
+            let warnings = find_warnings([&diff], &config);
+            (regressions.len(), warnings.len())
+        };
+
+        // OK, WARN and FAIL
+        assert_eq!(outcome("101.0"), (0, 0));
+        assert_eq!(outcome("103.0"), (0, 1));
+        assert_eq!(outcome("106.0"), (1, 0));
+
+        assert!(report_outcome(&config, vec![], vec![], vec!["slower".to_string()]).is_ok());
+        assert!(matches!(
+            report_outcome(&config, vec!["slower".to_string()], vec![], vec![]),
+            Err(RunError::Regression { count: 1 })
+        ));
+    }
+
+    #[test]
+    fn json_baseline_against_a_text_run() {
+        let baseline = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/baseline.json");
+        let current = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/new.txt");
+        let config = config(&["--baseline-json", baseline, current]);
+        assert_eq!(config.old_file, baseline);
+        assert_eq!(config.new_file, current);
+
+        let mut parsed = parse_inputs(&config, &[&config.old_file, &config.new_file]);
+        let (new_results, _) = parsed.pop().unwrap().unwrap();
+        let (old_results, errors) = parsed.pop().unwrap().unwrap();
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(old_results.len(), 2);
+
+        let comparisons = compare_benchmark_results_by(old_results, new_results, &config.match_key);
+        let diffs: Vec<&BenchDiff> = comparisons.iter().filter_map(|c| c.as_changed()).collect();
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[0].name, "com.example.Bench.alpha");
+        assert_eq!(diffs[0].old_score, 1000.0);
+        assert_eq!(diffs[0].new_score, 900.0);
+    }
+
+    #[test]
+    fn an_empty_include_list_matches_nothing() {
+        let result: BenchResult = "A.run avgt 5 1.0 ns/op".parse().unwrap();
+        let filters = Filters {
+            include: Some(HashSet::new()),
+            ..Filters::default()
+        };
+        assert!(!filters.is_selected(&result));
+        assert!(Filters::default().is_selected(&result));
+    }
+
+    #[test]
+    fn an_empty_exclude_list_excludes_nothing() {
+        let result: BenchResult = "A.run avgt 5 1.0 ns/op".parse().unwrap();
+        let filters = Filters {
+            exclude: HashSet::new(),
+            ..Filters::default()
+        };
+        assert!(filters.is_selected(&result));
+        let filters = Filters {
+            exclude: HashSet::from(["A.run".to_string()]),
+            ..Filters::default()
+        };
+        assert!(!filters.is_selected(&result));
+    }
+
+    #[test]
+    fn warmup_check_of_a_json_report() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/baseline.json");
+        let (results, _) = parse_json_file(path).unwrap();
+        assert_eq!(
+            warmup_warnings("baseline.json", &results, 5.0),
+            ["baseline.json: --warmup-check can't check any benchmarks, since warmup scores are only in the log of text output, not in JSON or CSV reports"]
+        );
+    }
+
+    #[test]
+    fn warmup_check_of_text_output() {
+        let mut warm: BenchResult = "A.run avgt 5 10.0 ns/op".parse().unwrap();
+        warm.warmup_scores = vec![10.1, 9.9];
+        let mut cold = warm.clone();
+        cold.name = "B.run".to_string();
+        cold.warmup_scores = vec![20.0];
+        let mut unknown = warm.clone();
+        unknown.warmup_scores = vec![];
+
+        assert_eq!(
+            warmup_warnings("new.txt", &[warm, cold, unknown], 5.0),
+            [
+                "new.txt: the last warmup iterations of B.run are +100.00% off its score, so it may not have been warmed up",
+                "new.txt: 1 benchmark(s) have no warmup scores, which are only in the log of text output"
+            ]
+        );
+    }
 }
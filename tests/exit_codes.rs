@@ -0,0 +1,72 @@
+use std::process::Command;
+
+/// Runs jmhcmp from the root of the crate, so the paths of the test data are relative to it.
+fn exit_code(args: &[&str]) -> i32 {
+    let output = Command::new(env!("CARGO_BIN_EXE_jmhcmp"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("jmhcmp runs");
+    output.status.code().expect("jmhcmp exits with a code")
+}
+
+#[test]
+fn no_regressions_exits_0() {
+    assert_eq!(exit_code(&["tests/data/old.txt", "tests/data/old.txt"]), 0);
+}
+
+#[test]
+fn regression_exits_1() {
+    assert_eq!(
+        exit_code(&[
+            "--threshold",
+            "5",
+            "tests/data/old.txt",
+            "tests/data/new.txt"
+        ]),
+        1
+    );
+}
+
+#[test]
+fn argument_error_exits_2() {
+    assert_eq!(
+        exit_code(&[
+            "--threshold",
+            "x",
+            "tests/data/old.txt",
+            "tests/data/new.txt"
+        ]),
+        2
+    );
+    assert_eq!(exit_code(&["tests/data/old.txt"]), 2);
+}
+
+#[test]
+fn unknown_option_exits_2() {
+    assert_eq!(
+        exit_code(&[
+            "--thresold",
+            "5",
+            "tests/data/old.txt",
+            "tests/data/new.txt"
+        ]),
+        2
+    );
+}
+
+#[test]
+fn unreadable_file_exits_3() {
+    assert_eq!(
+        exit_code(&["tests/data/old.txt", "tests/data/missing.txt"]),
+        3
+    );
+}
+
+#[test]
+fn strict_parse_failure_exits_4() {
+    assert_eq!(
+        exit_code(&["--strict", "tests/data/bad.txt", "tests/data/old.txt"]),
+        4
+    );
+}
@@ -0,0 +1,34 @@
+use std::process::Command;
+
+fn stdout(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_jmhcmp"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("jmhcmp runs");
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn class_headings_cover_the_rows_past_max_rows() {
+    let all = stdout(&[
+        "--group-by-class",
+        "--color",
+        "never",
+        "tests/data/old.txt",
+        "tests/data/new.txt",
+    ]);
+    let truncated = stdout(&[
+        "--group-by-class",
+        "--color",
+        "never",
+        "--max-rows",
+        "1",
+        "tests/data/old.txt",
+        "tests/data/new.txt",
+    ]);
+    let heading = "com.example.Bench: geomean improvement +1.13%";
+    assert!(all.contains(heading));
+    assert!(truncated.contains(heading));
+    assert!(!truncated.contains("com.example.Bench.beta"));
+}
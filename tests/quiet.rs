@@ -0,0 +1,39 @@
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_jmhcmp"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("jmhcmp runs")
+}
+
+#[test]
+fn quiet_within_noise_prints_one_line_and_exits_0() {
+    let output = run(&[
+        "--quiet",
+        "--noise",
+        "10",
+        "--threshold",
+        "10",
+        "tests/data/noise_old.txt",
+        "tests/data/noise_new.txt",
+    ]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "3 benchmarks compared, no significant changes\n"
+    );
+    assert!(output.stderr.is_empty());
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn quiet_with_a_regression_prints_the_table() {
+    let output = run(&[
+        "--quiet",
+        "tests/data/noise_old.txt",
+        "tests/data/noise_new.txt",
+    ]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("com.example.A.run"));
+    assert_eq!(output.status.code(), Some(1));
+}